@@ -1,39 +1,13 @@
-use actix_web::{get, web, App, HttpRequest, HttpResponse, HttpServer};
-use axum::async_trait;
+use actix_web::{get, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use db::User;
-use loginmanager::{AuthContext, AuthUser, CookieSession, CurrentUser, LoginManager};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, CsrfProtection, CsrfToken, CurrentUser, LoginManager,
+    LoginRequired, NextUrl,
+};
 use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, Value};
 use serde::Deserialize;
 mod db;
 
-#[async_trait(?Send)]
-impl loginmanager::UserMinix<HttpRequest> for User {
-    type Key = i32;
-    async fn get_user2(id: &Self::Key, req: &mut HttpRequest) -> Option<Self> {
-        let db = req.app_data::<web::Data<DatabaseConnection>>()?;
-
-        let user = db
-            .query_one(Statement::from_sql_and_values(
-                DatabaseBackend::Sqlite,
-                "select id,name,password from user where id=?",
-                [Value::from(id.to_string())],
-            ))
-            .await
-            .ok()??;
-
-        let user = Self {
-            id: user.try_get::<i32>("", "id").unwrap(),
-            name: user.try_get::<String>("", "name").unwrap(),
-            password: user.try_get::<String>("", "password").unwrap(),
-        };
-        Some(user)
-    }
-
-    fn get_id(&self) -> &Self::Key {
-        &self.id
-    }
-}
-
 #[get("/")]
 async fn index(AuthUser(user): AuthUser<User>) -> HttpResponse {
     HttpResponse::Ok().content_type("text/html").body(format!(
@@ -42,16 +16,22 @@ async fn index(AuthUser(user): AuthUser<User>) -> HttpResponse {
     ))
 }
 
-async fn login_get() -> HttpResponse {
-    HttpResponse::Ok().body(
+async fn hello_api(CurrentUser(user): CurrentUser<User>) -> HttpResponse {
+    HttpResponse::Ok().body(format!("Hello:{:?}", user.name))
+}
+
+async fn login_get(csrf_token: CsrfToken) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
         r#"
     <form method="POST">
+        <input name="csrf_token" type="hidden" value="{}"></input>
         username:<input name="username"></input>
         password:<input name="password" type="password"></input>
         <input type="submit"></input>
     </form>
     "#,
-    )
+        csrf_token.as_str()
+    ))
 }
 
 #[derive(Deserialize)]
@@ -62,7 +42,9 @@ struct UserForm {
 
 async fn login_post(
     mut auth_context: AuthContext,
+    next: NextUrl,
     db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
     form: web::Form<UserForm>,
 ) -> HttpResponse {
     let user = db
@@ -82,20 +64,18 @@ async fn login_post(
         };
         if user.password == form.password {
             auth_context.login(&user);
-            return HttpResponse::SeeOther()
-                .insert_header(("location", "/"))
-                .body("/");
+            next.respond_to(&req)
         } else {
-            return HttpResponse::Ok().body("error password");
+            HttpResponse::Ok().body("error password")
         }
     } else {
-        return HttpResponse::Ok().body(format!("{:?} not exists.", user));
+        HttpResponse::Ok().body(format!("{:?} not exists.", user))
     }
 }
 
 async fn login_out(
     mut auth_context: AuthContext,
-    CurrentUser(user): CurrentUser<User>,
+    CurrentUser(_user): CurrentUser<User>,
 ) -> HttpResponse {
     auth_context.logout();
     HttpResponse::SeeOther()
@@ -109,13 +89,24 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(conn.clone()))
-            .wrap(LoginManager::new(
-                CookieSession::new("secret").secure(false),
-            ))
+            .wrap(
+                LoginManager::new(CookieSession::new("secret").secure(false))
+                    .user_loader(|id: i32, db: DatabaseConnection| db::find_user(db, id)),
+            )
             .service(index)
-            .route("/login", web::get().to(login_get))
-            .route("/login", web::post().to(login_post))
+            .service(
+                web::scope("/login")
+                    .wrap(CsrfProtection::new())
+                    .route("", web::get().to(login_get))
+                    .route("", web::post().to(login_post)),
+            )
             .route("/logout", web::get().to(login_out))
+            .service(
+                web::scope("/api")
+                    .wrap(LoginRequired::<User>::new().allow("/api/public"))
+                    .route("/hello", web::get().to(hello_api))
+                    .route("/public", web::get().to(|| async { "Hello, World!" })),
+            )
         // .service(echo)
         // .route("/hey", web::get().to(manual_hello))
     })
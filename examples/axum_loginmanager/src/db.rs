@@ -1,4 +1,5 @@
-use sea_orm::{ConnectionTrait, Database, DatabaseBackend, DatabaseConnection, Statement};
+use loginmanager::LoaderError;
+use sea_orm::{ConnectionTrait, Database, DatabaseBackend, DatabaseConnection, Statement, Value};
 
 #[derive(Debug, Clone)]
 pub struct User {
@@ -7,6 +8,42 @@ pub struct User {
     pub password: String,
 }
 
+// loaded by `find_user`, registered with `LoginManager::user_loader`
+impl loginmanager::UserMinix for User {
+    type Key = i32;
+    type State = DatabaseConnection;
+
+    fn get_id(&self) -> &Self::Key {
+        &self.id
+    }
+}
+
+pub async fn find_user(db: DatabaseConnection, id: i32) -> Result<Option<User>, LoaderError> {
+    // a database outage is answered with 503 instead of the login page
+    let user = db
+        .query_one(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "select id,name,password from user where id=?",
+            [Value::from(id.to_string())],
+        ))
+        .await
+        .map_err(LoaderError::unavailable)?;
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let user = User {
+        id: user.try_get::<i32>("", "id").map_err(LoaderError::new)?,
+        name: user
+            .try_get::<String>("", "name")
+            .map_err(LoaderError::new)?,
+        password: user
+            .try_get::<String>("", "password")
+            .map_err(LoaderError::new)?,
+    };
+    Ok(Some(user))
+}
+
 pub async fn get_db() -> DatabaseConnection {
     let conn: DatabaseConnection = Database::connect("sqlite::memory:").await.unwrap();
     conn.execute(Statement::from_string(
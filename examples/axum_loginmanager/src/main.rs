@@ -1,63 +1,38 @@
-use std::sync::Arc;
 mod db;
 use axum::{
-    async_trait,
-    middleware::from_extractor,
+    extract::{FromRef, State},
     response::{Html, IntoResponse, Redirect, Response},
     routing::get,
-    Extension, Form, Router,
+    Form, Router,
 };
 use db::User;
-use loginmanager::{AuthContext, AuthUser, CookieSession, CurrentUser, LoginManager};
+use loginmanager::{
+    AuthContext, AuthUser, CsrfProtection, CsrfToken, CurrentUser, LoginManager, LoginRequired,
+    NextUrl, SqliteStore, StoreSession, UserCache,
+};
 use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, Value};
 use serde::Deserialize;
 
-#[async_trait]
-impl loginmanager::UserMinix<axum::http::request::Parts> for User {
-    type Key = i32;
-
-    async fn get_user(id: &Self::Key, req: &mut axum::http::request::Parts) -> Option<Self> {
-        let state = req.extensions.get::<Arc<AppState>>()?;
-        let user = state
-            .db()
-            .query_one(Statement::from_sql_and_values(
-                DatabaseBackend::Sqlite,
-                "select id,name,password from user where id=?",
-                [Value::from(id.to_string())],
-            ))
-            .await
-            .ok()??;
-        let user = Self {
-            id: user.try_get::<i32>("", "id").unwrap(),
-            name: user.try_get::<String>("", "name").unwrap(),
-            password: user.try_get::<String>("", "password").unwrap(),
-        };
-        Some(user)
-    }
-
-    fn get_id(&self) -> &Self::Key {
-        &self.id
-    }
-}
-
 async fn hello_user(AuthUser(user): AuthUser<User>) -> Response {
-    return Html(format!(
+    Html(format!(
         "hello {}<br> <a href='/logout'>logout</a>",
         user.name
     ))
-    .into_response();
+    .into_response()
 }
 
-async fn login_get() -> impl IntoResponse {
-    return Html(
+async fn login_get(csrf_token: CsrfToken) -> impl IntoResponse {
+    Html(format!(
         r#"
     <form method="POST">
+        <input name="csrf_token" type="hidden" value="{}"></input>
         username:<input name="username"></input>
         password:<input name="password" type="password"></input>
         <input type="submit"></input>
     </form>
     "#,
-    );
+        csrf_token.as_str()
+    ))
 }
 
 #[derive(Deserialize)]
@@ -67,8 +42,9 @@ struct UserForm {
 }
 
 async fn login_post(
-    Extension(state): Extension<Arc<AppState>>,
+    State(state): State<AppState>,
     mut auth_context: AuthContext,
+    next: NextUrl,
     Form(form): Form<UserForm>,
 ) -> Response {
     let user = state
@@ -89,23 +65,24 @@ async fn login_post(
         };
         if user.password == form.password {
             auth_context.login(&user);
-            return Redirect::to("/").into_response();
+            next.into_response()
         } else {
-            return format!("error password").into_response();
+            "error password".to_string().into_response()
         }
     } else {
-        return format!("{:?} not exists.", user).into_response();
+        format!("{:?} not exists.", user).into_response()
     }
 }
 
 async fn login_out(
     mut auth_context: AuthContext,
-    CurrentUser(user): CurrentUser<User>,
+    CurrentUser(_user): CurrentUser<User>,
 ) -> impl IntoResponse {
     auth_context.logout();
-    return Redirect::to("/login");
+    Redirect::to("/login")
 }
 
+#[derive(Clone)]
 pub struct AppState {
     conn: DatabaseConnection,
 }
@@ -116,18 +93,40 @@ impl AppState {
     }
 }
 
+// the `UserMinix::State` of `User`
+impl FromRef<AppState> for DatabaseConnection {
+    fn from_ref(state: &AppState) -> Self {
+        state.conn.clone()
+    }
+}
+
 #[tokio::main]
 async fn main() {
+    let conn = db::get_db().await;
+    let state = AppState { conn: conn.clone() };
+
     // protect api
     let api = Router::new()
         .route("/:path", get(hello_user))
-        .route_layer(from_extractor::<AuthUser<User>>());
+        .route("/public/:path", get(|| async { "Hello, World!" }))
+        .route_layer(
+            LoginRequired::<User>::new()
+                .allow("/public/*")
+                .with_state(state.clone()),
+        );
 
-    let loginmanager = LoginManager::new(CookieSession::new("secret").secure(false))
-        .redirect(true)
-        .login_view("/login");
+    // keep sessions in the same database
+    let store = SqliteStore::new(conn.clone());
+    store.migrate().await.unwrap();
+    store.spawn_cleanup(std::time::Duration::from_secs(600));
 
-    let conn = db::get_db().await;
+    let loginmanager = LoginManager::new(StoreSession::new(store).secure(false))
+        .redirect(true)
+        .login_view("/login")
+        // keep the users for a minute instead of loading them on every request
+        .user_cache(UserCache::new(1000))
+        // the `UserMinix::State` of `User`, a `FromRef` of the router state
+        .user_loader(|id: i32, db: DatabaseConnection| db::find_user(db, id));
 
     let app = Router::new()
         .nest("/api", api)
@@ -135,8 +134,9 @@ async fn main() {
         .route("/login", get(login_get).post(login_post))
         .route("/logout", get(login_out))
         .route("/common", get(|| async { "Hello, World!" }))
-        .layer(loginmanager)
-        .layer(Extension(Arc::new(AppState { conn })));
+        .with_state(state)
+        .layer(CsrfProtection::new())
+        .layer(loginmanager);
 
     // run it with hyper on localhost:3000
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+#[cfg(feature = "actix_layer")]
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    HttpMessage,
+};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{
+    body::Body,
+    response::{IntoResponse, Response},
+};
+#[cfg(feature = "axum_layer")]
+use http::{Request, StatusCode};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    loginmanager::{DecodeRequest, LoginInfo},
+    StoreError,
+};
+
+/// The number of hex chars of the key hash used to look a key up.
+pub const HASH_PREFIX_LEN: usize = 8;
+
+/// A stored API key, only the hash of the key is kept.
+#[derive(Debug, Clone)]
+pub struct ApiKeyRecord {
+    /// The hash from [`ApiKey::hash`].
+    pub hash: String,
+    /// The serialized user key.
+    pub user_key: String,
+    /// The scopes granted to the key, see [`LoginInfo::scopes`].
+    pub scopes: Vec<String>,
+}
+
+impl ApiKeyRecord {
+    /// Fails when the user key can not be serialized.
+    pub fn new<K: Serialize>(
+        hash: String,
+        user_key: &K,
+        scopes: Vec<String>,
+    ) -> Result<Self, StoreError> {
+        Ok(Self {
+            hash,
+            user_key: serde_json::to_string(user_key)?,
+            scopes,
+        })
+    }
+}
+
+/// Storage of API keys, e.g. a table indexed by the hash prefix.
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync + 'static {
+    /// Find the keys whose hash starts with `hash_prefix`.
+    ///
+    /// An error fails the request with `500 Internal Server Error`.
+    async fn find(&self, hash_prefix: &str) -> Result<Vec<ApiKeyRecord>, StoreError>;
+}
+
+/// Decode the user key from an API key in a header or query parameter.
+///
+/// Keys are hashed with sha256, the first [`HASH_PREFIX_LEN`] chars of the
+/// hash are used to find the candidates in the [`ApiKeyStore`], then the
+/// full hashes are compared in constant time.
+pub struct ApiKey<S> {
+    store: Arc<S>,
+    header: Option<String>,
+    query: Option<String>,
+}
+
+impl<S> Clone for ApiKey<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            header: self.header.clone(),
+            query: self.query.clone(),
+        }
+    }
+}
+
+impl ApiKey<()> {
+    /// Generate a new random API key, give it to the client and store its [`ApiKey::hash`].
+    pub fn generate() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    /// Hash an API key for storage.
+    pub fn hash(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// The prefix of a hash used to look the key up.
+    pub fn hash_prefix(hash: &str) -> &str {
+        &hash[..HASH_PREFIX_LEN.min(hash.len())]
+    }
+}
+
+impl<S: ApiKeyStore> ApiKey<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            header: Some("x-api-key".to_owned()),
+            query: None,
+        }
+    }
+
+    /// Read the key from this header, Default: `x-api-key`
+    pub fn header(mut self, header: Option<&str>) -> Self {
+        self.header = header.map(|h| h.to_ascii_lowercase());
+        self
+    }
+
+    /// Read the key from this query parameter, Default: `None`
+    pub fn query(mut self, query: Option<&str>) -> Self {
+        self.query = query.map(ToOwned::to_owned);
+        self
+    }
+
+    fn key_from_query(&self, query: Option<&str>) -> Option<String> {
+        let name = self.query.as_deref()?;
+        query?.split('&').find_map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            if k == name {
+                urlencoding::decode(v).ok().map(|v| v.into_owned())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Verify the key, return the user key and scopes.
+    async fn verify(&self, key: String) -> Result<Option<(String, Vec<String>)>, StoreError> {
+        let hash = ApiKey::hash(&key);
+        let records = self.store.find(ApiKey::hash_prefix(&hash)).await?;
+        Ok(records
+            .into_iter()
+            .find(|record| bool::from(record.hash.as_bytes().ct_eq(hash.as_bytes())))
+            .map(|record| (record.user_key, record.scopes)))
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<S: ApiKeyStore> DecodeRequest<Request<Body>, Response> for ApiKey<S> {
+    async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+        let key = self
+            .header
+            .as_ref()
+            .and_then(|h| req.headers().get(h.as_str()))
+            .and_then(|v| v.to_str().ok())
+            .map(ToOwned::to_owned)
+            .or_else(|| self.key_from_query(req.uri().query()));
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        match self.verify(key).await {
+            Ok(Some((user_key, scopes))) => {
+                let login_info = req.extensions().get::<LoginInfo>().unwrap();
+                login_info.set_scopes(scopes);
+                Ok(Some(user_key))
+            }
+            Ok(None) => Ok(None),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        }
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+#[async_trait(?Send)]
+impl<S: ApiKeyStore> DecodeRequest<ServiceRequest, ServiceResponse> for ApiKey<S> {
+    async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
+        let key = self
+            .header
+            .as_ref()
+            .and_then(|h| req.headers().get(h.as_str()))
+            .and_then(|v| v.to_str().ok())
+            .map(ToOwned::to_owned)
+            .or_else(|| self.key_from_query(req.uri().query()));
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        match self.verify(key).await {
+            Ok(Some((user_key, scopes))) => {
+                let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+                login_info.set_scopes(scopes);
+                Ok(Some(user_key))
+            }
+            Ok(None) => Ok(None),
+            Err(_) => Err(ServiceResponse::new(
+                req.request().clone(),
+                actix_web::HttpResponse::InternalServerError().finish(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn record_key() {
+        let record = ApiKeyRecord::new("hash".to_owned(), &1, vec![]).unwrap();
+        assert_eq!(record.user_key, "1");
+        // a map key must be a string in json
+        let key = HashMap::from([((1, 2), 3)]);
+        assert!(ApiKeyRecord::new("hash".to_owned(), &key, vec![]).is_err());
+    }
+}
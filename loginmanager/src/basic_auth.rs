@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+#[cfg(feature = "actix_layer")]
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    HttpMessage,
+};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{body::Body, response::Response};
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "axum_layer")]
+use http::{header, HeaderValue, Request, StatusCode};
+use serde::Serialize;
+
+use crate::loginmanager::{DecodeRequest, LoginInfo};
+
+/// Check the credentials of HTTP Basic authentication.
+///
+/// ## Example
+/// ```
+/// use loginmanager::BasicVerifier;
+///
+/// struct Verifier;
+///
+/// #[async_trait::async_trait]
+/// impl BasicVerifier for Verifier {
+///     type Key = i32;
+///
+///     async fn verify(&self, username: &str, password: &str) -> Option<i32> {
+///         (username == "admin" && password == "secret").then_some(1)
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait BasicVerifier: Send + Sync + 'static {
+    /// The type of User key, must be same as `UserMinix::Key`.
+    type Key: Serialize;
+
+    /// Resolve the credentials to the user key, `None` if they are invalid.
+    async fn verify(&self, username: &str, password: &str) -> Option<Self::Key>;
+}
+
+/// Decode the user key from `Authorization: Basic <credentials>`.
+///
+/// Unauthorized responses get a `WWW-Authenticate` challenge, and the
+/// `LoginManager` does not redirect them to `login_view`. Next to a session
+/// decoder, e.g. `FirstOf<(CookieSession, BasicAuth<V>)>`, the challenge is only
+/// sent to requests with `Authorization: Basic`, so browsers are still
+/// redirected to the login page instead of getting the Basic popup.
+pub struct BasicAuth<V> {
+    verifier: Arc<V>,
+    realm: String,
+    challenge: bool,
+}
+
+impl<V> Clone for BasicAuth<V> {
+    fn clone(&self) -> Self {
+        Self {
+            verifier: self.verifier.clone(),
+            realm: self.realm.clone(),
+            challenge: self.challenge,
+        }
+    }
+}
+
+impl<V: BasicVerifier> BasicAuth<V> {
+    pub fn new(verifier: V) -> Self {
+        Self {
+            verifier: Arc::new(verifier),
+            realm: "Restricted".to_owned(),
+            challenge: true,
+        }
+    }
+
+    /// The realm of the challenge, Default: `Restricted`
+    ///
+    /// Only visible ASCII and spaces can be sent in the header, other chars are
+    /// left out, and `"` and `\` are escaped.
+    pub fn realm<S: Into<String>>(mut self, realm: S) -> Self {
+        self.realm = realm
+            .into()
+            .chars()
+            .filter(|c| c.is_ascii_graphic() || *c == ' ')
+            .fold(String::new(), |mut realm, c| {
+                if c == '"' || c == '\\' {
+                    realm.push('\\');
+                }
+                realm.push(c);
+                realm
+            });
+        self
+    }
+
+    /// Send the challenge on 401 responses, Default `true`.
+    ///
+    /// Set false to never send it, see [`LoginInfo::accepts_challenge`].
+    pub fn challenge(mut self, challenge: bool) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    /// A valid header value, the realm is cleaned by [`BasicAuth::realm`].
+    fn challenge_value(&self) -> String {
+        format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm)
+    }
+
+    /// Remember a Basic attempt so a failed one is answered with the challenge.
+    fn attempted(authorization: Option<&str>, login_info: &LoginInfo) {
+        if authorization.is_some_and(|v| v.starts_with("Basic ")) {
+            login_info.set_accepts_challenge(true);
+        }
+    }
+
+    /// Verify the `Authorization` header value and get the user key.
+    async fn key_from(&self, authorization: Option<String>) -> Option<String> {
+        let credentials = authorization?.strip_prefix("Basic ")?.trim().to_owned();
+        let credentials = String::from_utf8(STANDARD.decode(credentials).ok()?).ok()?;
+        let (username, password) = credentials.split_once(':')?;
+        let key = self.verifier.verify(username, password).await?;
+        serde_json::to_string(&key).ok()
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<V: BasicVerifier> DecodeRequest<Request<Body>, Response> for BasicAuth<V> {
+    async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(ToOwned::to_owned);
+        Self::attempted(
+            authorization.as_deref(),
+            req.extensions().get::<LoginInfo>().unwrap(),
+        );
+        Ok(self.key_from(authorization).await)
+    }
+
+    async fn update(&self, res: &mut Response) {
+        let accepts_challenge = res
+            .extensions()
+            .get::<LoginInfo>()
+            .is_some_and(LoginInfo::accepts_challenge);
+        if !(self.challenge && accepts_challenge && res.status() == StatusCode::UNAUTHORIZED) {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.challenge_value()) {
+            res.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+        }
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+#[async_trait(?Send)]
+impl<V: BasicVerifier> DecodeRequest<ServiceRequest, ServiceResponse> for BasicAuth<V> {
+    async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
+        let authorization = req
+            .headers()
+            .get(actix_web::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(ToOwned::to_owned);
+        Self::attempted(
+            authorization.as_deref(),
+            req.extensions().get::<LoginInfo>().unwrap(),
+        );
+        Ok(self.key_from(authorization).await)
+    }
+
+    async fn update2(&self, res: &mut ServiceResponse) {
+        let accepts_challenge = res
+            .request()
+            .extensions()
+            .get::<LoginInfo>()
+            .is_some_and(LoginInfo::accepts_challenge);
+        if !(self.challenge
+            && accepts_challenge
+            && res.status() == actix_web::http::StatusCode::UNAUTHORIZED)
+        {
+            return;
+        }
+        if let Ok(value) = actix_web::http::header::HeaderValue::from_str(&self.challenge_value()) {
+            res.headers_mut()
+                .insert(actix_web::http::header::WWW_AUTHENTICATE, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Verifier;
+
+    #[async_trait]
+    impl BasicVerifier for Verifier {
+        type Key = i32;
+
+        async fn verify(&self, _: &str, _: &str) -> Option<i32> {
+            None
+        }
+    }
+
+    #[test]
+    fn realm_header() {
+        let basic = BasicAuth::new(Verifier).realm("Café \"admin\"\\\r\n\tarea");
+        assert_eq!(
+            basic.challenge_value(),
+            r#"Basic realm="Caf \"admin\"\\area", charset="UTF-8""#
+        );
+        assert!(http::HeaderValue::from_str(&basic.challenge_value()).is_ok());
+    }
+}
@@ -12,9 +12,11 @@ use sha2::digest::FixedOutput;
 use sha2::{Digest, Sha256};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use time::{Duration, OffsetDateTime};
 
 use crate::loginmanager::{DecodeRequest, LoginInfo};
+use crate::store::random_id;
 
 /// use cookie as session to storage the info of user key.
 #[derive(Clone)]
@@ -28,6 +30,8 @@ pub struct CookieSession {
     max_age: Option<Duration>,
     expires_in: Option<Duration>,
     same_site: Option<SameSite>,
+    absolute_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
 }
 
 impl CookieSession {
@@ -45,6 +49,8 @@ impl CookieSession {
             max_age: None,
             expires_in: None,
             same_site: None,
+            absolute_timeout: Some(Duration::days(30)),
+            idle_timeout: None,
         }
     }
 
@@ -88,28 +94,120 @@ impl CookieSession {
         self
     }
 
-    fn get_session_from(&self, headers: &HeaderMap<HeaderValue>) -> Option<Session> {
-        let mut cookie_find = "".to_owned();
-        for hdr in headers.get_all(header::COOKIE) {
-            let s = hdr.to_str().unwrap();
-            for cookie_str in s.split(';').map(|s| s.trim()) {
-                if !cookie_str.is_empty() && cookie_str.starts_with(&format!("{}=", self.name)) {
-                    cookie_find = cookie_str.to_string();
-                }
-            }
+    /// The session is rejected this long after login, whatever the cookie says.
+    /// Default: 30 days
+    ///
+    /// Sessions written before the login time was kept count from their next use.
+    pub fn absolute_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.absolute_timeout = timeout;
+        self
+    }
+
+    /// The session is rejected when it is not used for this long, Default `None`.
+    ///
+    /// The activity is written to the cookie at most once per half of the timeout.
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    fn is_expired(&self, session: &Session) -> bool {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        // sessions written before the timestamps existed are stamped by `accept`
+        let expired = |at: Option<i64>, timeout: Option<Duration>| match (at, timeout) {
+            (Some(at), Some(timeout)) => now - at > timeout.whole_seconds(),
+            _ => false,
+        };
+        // anonymous sessions only carry `SessionData`
+        (session.user_id.is_some() && expired(session.login_at, self.absolute_timeout))
+            || expired(session.last_seen, self.idle_timeout)
+    }
+
+    /// Check the session, save its state in the login info and get the user key.
+    fn accept(
+        &self,
+        mut session: Session,
+        fingerprint: &str,
+        login_info: &LoginInfo,
+    ) -> Option<String> {
+        if session.fingerprint.is_empty() {
+            // a cookie of 0.2, rewritten with a random id as it has no `last_seen`
+            session.fingerprint = std::mem::replace(&mut session.id, random_id());
         }
-        match Cookie::parse_encoded(cookie_find) {
-            Ok(cookie) => {
-                let mut jar = CookieJar::new();
-                jar.add_original(cookie.clone());
-                if let Some(cookie) = jar.private(&self.key).get(&self.name) {
-                    serde_json::from_str::<Session>(cookie.value()).ok()
-                } else {
-                    None
-                }
-            }
-            _ => None,
+        if session.fingerprint != fingerprint || self.is_expired(&session) {
+            return None;
+        }
+        login_info.set_session_id(Some(session.id));
+        login_info.set_data(session.data);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let login_at = session.login_at.or(session.user_id.as_ref().map(|_| now));
+        login_info.set_login_at(login_at);
+        // a missing `last_seen` rewrites the cookie with the stamped `login_at`
+        login_info.set_last_seen(session.last_seen);
+        login_info.set_fresh(session.fresh);
+        if session.user_id.is_some() {
+            login_info.set_epoch(Some(session.epoch));
+        }
+        session.user_id
+    }
+
+    /// The user key the cookie must be written with, also write it when the
+    /// data changed or half of the idle timeout or max-age has passed.
+    fn pending_key(&self, login_info: &LoginInfo) -> Option<Option<String>> {
+        if login_info.is_data_only() {
+            return login_info.is_data_changed().then_some(None);
+        }
+        if let Some(key) = login_info.pending_key() {
+            return Some(key);
+        }
+        // only keep the user of a session read from the cookie
+        let key = login_info.session_id().and(login_info.get_key());
+        if login_info.is_data_changed() {
+            return Some(key);
         }
+        let key = key?;
+        let window = self.idle_timeout.or(self.max_age);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let renew = match (login_info.last_seen(), window) {
+            // written before the timestamps existed
+            (None, _) => true,
+            (Some(at), Some(window)) => now - at > window.whole_seconds() / 2,
+            (Some(_), None) => false,
+        };
+        renew.then_some(Some(key))
+    }
+
+    fn create_session(
+        &self,
+        key: Option<String>,
+        fingerprint: String,
+        login_info: &LoginInfo,
+    ) -> Session {
+        Session {
+            id: random_id(),
+            fingerprint,
+            user_id: key,
+            login_at: login_info.login_at_timestamp(),
+            last_seen: Some(OffsetDateTime::now_utc().unix_timestamp()),
+            fresh: login_info.is_fresh(),
+            epoch: login_info.epoch().flatten(),
+            data: login_info.data(),
+        }
+    }
+
+    fn get_session_from(&self, headers: &HeaderMap<HeaderValue>) -> Option<Session> {
+        let values = headers
+            .get_all(header::COOKIE)
+            .into_iter()
+            .filter_map(|hdr| hdr.to_str().ok());
+        self.open_session(find_cookie(values, &self.name)?)
+    }
+
+    fn open_session(&self, cookie: Cookie<'static>) -> Option<Session> {
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+        let cookie = jar.private(&self.key).get(&self.name)?;
+        serde_json::from_str::<Session>(cookie.value()).ok()
     }
 
     fn create_cookie(&self, session: Session) -> CookieJar {
@@ -140,10 +238,47 @@ impl CookieSession {
     }
 }
 
+/// Find the last cookie named `name` in the values of `Cookie` headers.
+pub(crate) fn find_cookie<'a, I>(values: I, name: &str) -> Option<Cookie<'static>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let prefix = format!("{}=", name);
+    let mut cookie_find = None;
+    for s in values {
+        for cookie_str in s.split(';').map(|s| s.trim()) {
+            if cookie_str.starts_with(&prefix) {
+                cookie_find = Some(cookie_str.to_owned());
+            }
+        }
+    }
+    Cookie::parse_encoded(cookie_find?).ok()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Session {
+    /// random, renewed on every login
     id: String,
+    /// hash of the client, see `_create_identifier`
+    /// empty for a cookie of 0.2, its `id` was the hash
+    #[serde(default)]
+    fingerprint: String,
     user_id: Option<String>,
+    /// unix time the credentials were entered, see `LoginInfo::login_at`
+    /// the absolute timeout is counted from it
+    #[serde(default)]
+    login_at: Option<i64>,
+    /// unix time of the last write, the idle timeout is counted from it
+    #[serde(default)]
+    last_seen: Option<i64>,
+    #[serde(default)]
+    fresh: bool,
+    /// see `UserMinix::session_epoch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    epoch: Option<String>,
+    /// see `SessionData`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    data: Map<String, Value>,
 }
 
 impl CookieSession {
@@ -165,7 +300,7 @@ impl CookieSession {
                 hasher.update("host-fake");
             };
         };
-        return hex::encode(hasher.finalize_fixed());
+        hex::encode(hasher.finalize_fixed())
     }
 
     #[cfg(feature = "actix_layer")]
@@ -181,36 +316,37 @@ impl CookieSession {
                 hasher.update(agent);
             };
         };
-        return hex::encode(hasher.finalize_fixed());
+        hex::encode(hasher.finalize_fixed())
     }
 }
 
 #[cfg(feature = "axum_layer")]
 #[async_trait]
 impl DecodeRequest<Request<Body>, Response> for CookieSession {
+    fn is_session(&self) -> bool {
+        true
+    }
+
     async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
         let login_info = req.extensions().get::<LoginInfo>().unwrap();
         let session = self.get_session_from(req.headers());
-        let id = Self::_create_identifier(req.headers());
-        let res = Ok(session.map_or(None, |s| if s.id == id { s.user_id } else { None }));
-        login_info.set_ext(Some(id));
-        res
+        let fingerprint = Self::_create_identifier(req.headers());
+        let key = session.and_then(|s| self.accept(s, &fingerprint, login_info));
+        login_info.set_ext(Some(fingerprint));
+        Ok(key)
     }
 
     async fn update(&self, res: &mut Response) {
         let login_info = res.extensions().get::<LoginInfo>().unwrap();
-        let key = if login_info.is_logout() {
-            None
-        } else if login_info.is_login() {
-            login_info.login_key()
-        } else {
-            return;
+        let key = match self.pending_key(login_info) {
+            Some(key) => key,
+            None => return,
         };
 
-        let id = login_info
+        let fingerprint = login_info
             .ext()
             .unwrap_or(Self::_create_identifier(res.headers()));
-        let session = Session { id, user_id: key };
+        let session = self.create_session(key, fingerprint, login_info);
 
         let jar = self.create_cookie(session);
 
@@ -226,36 +362,19 @@ impl DecodeRequest<Request<Body>, Response> for CookieSession {
 #[cfg(feature = "actix_layer")]
 #[async_trait(?Send)]
 impl DecodeRequest<ServiceRequest, ServiceResponse> for CookieSession {
+    fn is_session(&self) -> bool {
+        true
+    }
+
     async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
-        let mut cookie_find = "".to_owned();
-        for hdr in req.headers().get_all(actix_web::http::header::COOKIE) {
-            let s = hdr.to_str().unwrap();
-            for cookie_str in s.split(';').map(|s| s.trim()) {
-                if !cookie_str.is_empty() && cookie_str.starts_with(&format!("{}=", self.name)) {
-                    cookie_find = cookie_str.to_string();
-                }
-            }
-        }
-        let session = match Cookie::parse_encoded(cookie_find) {
-            Ok(cookie) => {
-                let mut jar = CookieJar::new();
-                jar.add_original(cookie.clone());
-                if let Some(cookie) = jar.private(&self.key).get(&self.name) {
-                    serde_json::from_str::<Session>(cookie.value()).ok()
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        };
-        Ok(session.map_or(None, |s| {
-            let id = Self::_create_identifier_actix(req.request());
-            if s.id == id {
-                s.user_id
-            } else {
-                None
-            }
-        }))
+        let values = req
+            .headers()
+            .get_all(actix_web::http::header::COOKIE)
+            .filter_map(|hdr| hdr.to_str().ok());
+        let session = find_cookie(values, &self.name).and_then(|c| self.open_session(c));
+        let fingerprint = Self::_create_identifier_actix(req.request());
+        let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+        Ok(session.and_then(|s| self.accept(s, &fingerprint, &login_info)))
     }
 
     async fn update2(&self, res: &mut ServiceResponse) {
@@ -265,17 +384,12 @@ impl DecodeRequest<ServiceRequest, ServiceResponse> for CookieSession {
             .get::<LoginInfo>()
             .unwrap()
             .clone();
-        let key = if logininfo.is_logout() {
-            None
-        } else if logininfo.is_login() {
-            logininfo.login_key()
-        } else {
-            return;
-        };
-        let session = Session {
-            id: Self::_create_identifier_actix(res.request()),
-            user_id: key,
+        let key = match self.pending_key(&logininfo) {
+            Some(key) => key,
+            None => return,
         };
+        let fingerprint = Self::_create_identifier_actix(res.request());
+        let session = self.create_session(key, fingerprint, &logininfo);
 
         let jar = self.create_cookie(session);
 
@@ -288,3 +402,127 @@ impl DecodeRequest<ServiceRequest, ServiceResponse> for CookieSession {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session of user 1, logged in `login_at` and written `last_seen` minutes ago.
+    fn session(login_at: i64, last_seen: i64) -> Session {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Session {
+            id: "a".to_owned(),
+            fingerprint: "f".to_owned(),
+            user_id: Some("1".to_owned()),
+            login_at: Some(now - login_at * 60),
+            last_seen: Some(now - last_seen * 60),
+            fresh: true,
+            epoch: None,
+            data: Map::new(),
+        }
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let cookie_session = CookieSession::new("secret").idle_timeout(Some(Duration::hours(1)));
+        let accept = |session| cookie_session.accept(session, "f", &LoginInfo::default());
+        assert_eq!(accept(session(70, 10)).as_deref(), Some("1"));
+        assert_eq!(accept(session(70, 61)), None);
+        // without idle timeout
+        let cookie_session = CookieSession::new("secret");
+        let key = cookie_session.accept(session(70, 61), "f", &LoginInfo::default());
+        assert_eq!(key.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn absolute_timeout() {
+        let cookie_session = CookieSession::new("secret")
+            .absolute_timeout(Some(Duration::hours(8)))
+            .idle_timeout(Some(Duration::hours(1)));
+        let accept = |session| cookie_session.accept(session, "f", &LoginInfo::default());
+        assert_eq!(accept(session(7 * 60, 0)).as_deref(), Some("1"));
+        // active all along
+        assert_eq!(accept(session(8 * 60 + 1, 0)), None);
+        // an anonymous session only carries data
+        let anonymous = Session {
+            user_id: None,
+            ..session(8 * 60 + 1, 0)
+        };
+        let login_info = LoginInfo::default();
+        assert_eq!(cookie_session.accept(anonymous, "f", &login_info), None);
+        assert!(login_info.session_id().is_some());
+    }
+
+    #[test]
+    fn activity_extends_idle() {
+        let cookie_session = CookieSession::new("secret").idle_timeout(Some(Duration::hours(1)));
+        let login_info = LoginInfo::default();
+        let key = cookie_session.accept(session(50, 10), "f", &login_info);
+        login_info.set_key(key);
+        // written at most once per half of the timeout
+        assert_eq!(cookie_session.pending_key(&login_info), None);
+
+        let login_info = LoginInfo::default();
+        let key = cookie_session.accept(session(50, 50), "f", &login_info);
+        login_info.set_key(key);
+        let key = cookie_session.pending_key(&login_info).unwrap();
+        assert_eq!(key.as_deref(), Some("1"));
+        let mut renewed = cookie_session.create_session(key, "f".to_owned(), &login_info);
+        assert_eq!(renewed.login_at, session(50, 0).login_at);
+        assert!(renewed.fresh);
+
+        // 20 minutes later the old session is expired, the renewed one is not
+        let mut old = session(50, 50);
+        for session in [&mut old, &mut renewed] {
+            session.last_seen = session.last_seen.map(|at| at - 20 * 60);
+        }
+        let accept = |session| cookie_session.accept(session, "f", &LoginInfo::default());
+        assert_eq!(accept(old), None);
+        assert_eq!(accept(renewed).as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn session_without_timestamps() {
+        let cookie_session = CookieSession::new("secret");
+        let login_info = LoginInfo::default();
+        let session = Session {
+            id: "a".to_owned(),
+            fingerprint: "f".to_owned(),
+            user_id: Some("1".to_owned()),
+            login_at: None,
+            last_seen: None,
+            fresh: false,
+            epoch: None,
+            data: Map::new(),
+        };
+        let key = cookie_session.accept(session, "f", &login_info);
+        assert_eq!(key.as_deref(), Some("1"));
+        assert!(login_info.login_at_timestamp().is_some());
+        // rewritten with the stamped timestamps
+        login_info.set_key(key);
+        assert_eq!(
+            cookie_session.pending_key(&login_info),
+            Some(Some("1".to_owned()))
+        );
+    }
+
+    #[test]
+    fn session_of_0_2() {
+        let cookie_session = CookieSession::new("secret");
+        let legacy = || serde_json::from_str::<Session>(r#"{"id":"f","user_id":"1"}"#).unwrap();
+        assert_eq!(
+            cookie_session.accept(legacy(), "other", &LoginInfo::default()),
+            None
+        );
+
+        let login_info = LoginInfo::default();
+        let key = cookie_session.accept(legacy(), "f", &login_info);
+        assert_eq!(key.as_deref(), Some("1"));
+        assert_ne!(login_info.session_id().as_deref(), Some("f"));
+        login_info.set_key(key);
+        assert_eq!(
+            cookie_session.pending_key(&login_info),
+            Some(Some("1".to_owned()))
+        );
+    }
+}
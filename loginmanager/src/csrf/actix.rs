@@ -0,0 +1,136 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web::{Bytes, BytesMut},
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::{future::LocalBoxFuture, StreamExt};
+
+use super::{get_or_create_token, CsrfProtection, CsrfToken};
+use crate::{loginmanager::LoginInfo, LoginError};
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CsrfMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfMiddleware {
+            service: Rc::new(service),
+            csrf: self.clone(),
+        }))
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: Rc<S>,
+    csrf: CsrfProtection,
+}
+
+impl<S> CsrfMiddleware<S> {
+    /// Read the form body, it is put back for the handler.
+    async fn read_form(&self, req: &mut ServiceRequest) -> Option<Bytes> {
+        let mut payload = req.take_payload();
+        let mut body = BytesMut::new();
+        while let Some(chunk) = payload.next().await {
+            body.extend_from_slice(&chunk.ok()?);
+            if body.len() > self.csrf.body_limit {
+                return None;
+            }
+        }
+        let body = body.freeze();
+        req.set_payload(Payload::from(body.clone()));
+        Some(body)
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let serv = self.service.clone();
+        if CsrfProtection::is_safe(req.method().as_str()) || self.csrf.is_exempt(req.path()) {
+            return Box::pin(async move { Ok(serv.call(req).await?.map_into_left_body()) });
+        }
+        let this = CsrfMiddleware {
+            service: serv.clone(),
+            csrf: self.csrf.clone(),
+        };
+
+        Box::pin(async move {
+            let login_info = req.extensions().get::<LoginInfo>().cloned();
+            let login_info = match login_info {
+                Some(login_info) => login_info,
+                None => {
+                    let res = HttpResponse::from_error(LoginError::MissingMiddleware);
+                    return Ok(req.into_response(res).map_into_right_body());
+                }
+            };
+            let header_str = |req: &ServiceRequest, name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToOwned::to_owned)
+            };
+            let mut token = header_str(&req, &this.csrf.header);
+            let content_type = header_str(&req, header::CONTENT_TYPE.as_str());
+            if token.is_none() && CsrfProtection::is_form(content_type.as_deref()) {
+                match this.read_form(&mut req).await {
+                    Some(body) => token = this.csrf.token_from_form(&body),
+                    None => {
+                        let res = HttpResponse::PayloadTooLarge().body("Payload too large.");
+                        return Ok(req.into_response(res).map_into_right_body());
+                    }
+                }
+            }
+            let same_origin = this.csrf.is_same_origin(
+                header_str(&req, header::ORIGIN.as_str()).as_deref(),
+                header_str(&req, header::REFERER.as_str()).as_deref(),
+                header_str(&req, header::HOST.as_str()).as_deref(),
+            );
+            if !this.csrf.is_valid(&login_info, token, same_origin) {
+                let res = HttpResponse::from_error(LoginError::Csrf);
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+            Ok(serv.call(req).await?.map_into_left_body())
+        })
+    }
+}
+
+impl actix_web::FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let login_info = req.extensions().get::<LoginInfo>().cloned();
+            login_info
+                .map(|info| Self(get_or_create_token(&info)))
+                .ok_or(LoginError::MissingMiddleware.into())
+        })
+    }
+}
@@ -0,0 +1,112 @@
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::FromRequestParts,
+    http::{header, request::Parts, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use tower_service::Service;
+
+use super::{get_or_create_token, CsrfProtection, CsrfToken};
+use crate::{loginmanager::LoginInfo, LoginError};
+
+impl<S> tower_layer::Layer<S> for CsrfProtection {
+    type Service = CsrfService<S>;
+
+    fn layer(&self, serv: S) -> Self::Service {
+        CsrfService {
+            serv,
+            csrf: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CsrfService<S> {
+    serv: S,
+    csrf: CsrfProtection,
+}
+
+impl<S> Service<Request<Body>> for CsrfService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + Clone + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.serv.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let _serv = self.serv.clone();
+        let mut serv = std::mem::replace(&mut self.serv, _serv);
+        if CsrfProtection::is_safe(req.method().as_str()) || self.csrf.is_exempt(req.uri().path()) {
+            return Box::pin(serv.call(req));
+        }
+        let csrf = self.csrf.clone();
+
+        Box::pin(async move {
+            let login_info = match req.extensions().get::<LoginInfo>().cloned() {
+                Some(login_info) => login_info,
+                None => return Ok(LoginError::MissingMiddleware.into_response()),
+            };
+            let header_str = |req: &Request<Body>, name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToOwned::to_owned)
+            };
+            let mut token = header_str(&req, &csrf.header);
+            let content_type = header_str(&req, header::CONTENT_TYPE.as_str());
+            let req = if token.is_none() && CsrfProtection::is_form(content_type.as_deref()) {
+                let (parts, body) = req.into_parts();
+                let bytes = match axum::body::to_bytes(body, csrf.body_limit).await {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        return Ok(
+                            (StatusCode::PAYLOAD_TOO_LARGE, "Payload too large.").into_response()
+                        )
+                    }
+                };
+                token = csrf.token_from_form(&bytes);
+                Request::from_parts(parts, Body::from(bytes))
+            } else {
+                req
+            };
+            let same_origin = csrf.is_same_origin(
+                header_str(&req, header::ORIGIN.as_str()).as_deref(),
+                header_str(&req, header::REFERER.as_str()).as_deref(),
+                header_str(&req, header::HOST.as_str())
+                    .or_else(|| req.uri().authority().map(ToString::to_string))
+                    .as_deref(),
+            );
+            if !csrf.is_valid(&login_info, token, same_origin) {
+                return Ok(LoginError::Csrf.into_response());
+            }
+            serv.call(req).await
+        })
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<LoginInfo>()
+            .map(|info| Self(get_or_create_token(info)))
+            .ok_or(LoginError::MissingMiddleware)
+    }
+}
@@ -0,0 +1,253 @@
+#[cfg(feature = "actix_layer")]
+mod actix;
+#[cfg(feature = "axum_layer")]
+mod axum;
+
+use std::sync::Arc;
+
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+
+use crate::loginmanager::LoginInfo;
+
+/// The key of the token in the `SessionData`.
+const CSRF_KEY: &str = "_csrf_token";
+
+/// The token of the session, `None` until a [`CsrfToken`] is extracted.
+fn session_token(login_info: &LoginInfo) -> Option<String> {
+    login_info.with_data(|data| data.get(CSRF_KEY)?.as_str().map(ToOwned::to_owned))
+}
+
+fn get_or_create_token(login_info: &LoginInfo) -> String {
+    if let Some(token) = session_token(login_info) {
+        return token;
+    }
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    login_info.update_data(|data| data.insert(CSRF_KEY.to_owned(), token.clone().into()));
+    token
+}
+
+/// Drop the token on login and logout, the next [`CsrfToken`] issues a new one.
+pub(crate) fn rotate_token(login_info: &LoginInfo) {
+    if login_info.with_data(|data| data.contains_key(CSRF_KEY)) {
+        login_info.update_data(|data| data.remove(CSRF_KEY));
+    }
+}
+
+/// `CsrfToken` Extractor
+///
+/// The CSRF token of the session, render it in forms as the field
+/// `csrf_token` or send it in the header `x-csrf-token`.
+/// It is kept in the session, a decoder writing `SessionData` is required.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// CsrfProtection rejects unsafe requests (`POST`, `PUT`, `DELETE`, ...)
+/// without the CSRF token of the session, use it inside the `LoginManager`.
+///
+/// The token is read from the header, or from the field of an
+/// `application/x-www-form-urlencoded` body. Once the session has a token
+/// it is required, sessions without one (no [`CsrfToken`] was extracted yet)
+/// pass when their `Origin` (or `Referer`) is the same host or a trusted
+/// origin. Rejected requests get 403, [`crate::LoginError::Csrf`].
+///
+/// ## Example
+/// ```no_run
+/// # use axum::{routing::post, Router};
+/// # use loginmanager::{CookieSession, CsrfProtection, LoginManager};
+/// # async fn login_post() {}
+/// # let loginmanager = LoginManager::new(CookieSession::new("secret"));
+/// let app: Router = Router::new()
+///     .route("/login", post(login_post))
+///     .layer(CsrfProtection::new().exempt("/webhook/*"))
+///     .layer(loginmanager);
+/// ```
+#[derive(Clone)]
+pub struct CsrfProtection {
+    exempt: Arc<Vec<String>>,
+    trusted_origins: Arc<Vec<String>>,
+    header: String,
+    field: String,
+    body_limit: usize,
+}
+
+impl Default for CsrfProtection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsrfProtection {
+    pub fn new() -> Self {
+        Self {
+            exempt: Arc::new(Vec::new()),
+            trusted_origins: Arc::new(Vec::new()),
+            header: "x-csrf-token".to_owned(),
+            field: "csrf_token".to_owned(),
+            body_limit: 1024 * 1024,
+        }
+    }
+
+    /// Let the path pass without token, a trailing `*` matches any suffix,
+    /// e.g. `/webhook/*`.
+    pub fn exempt<S: Into<String>>(mut self, path: S) -> Self {
+        Arc::make_mut(&mut self.exempt).push(path.into());
+        self
+    }
+
+    /// Let requests from this origin pass without token, e.g. `https://app.example.com`.
+    pub fn trusted_origin<S: Into<String>>(mut self, origin: S) -> Self {
+        Arc::make_mut(&mut self.trusted_origins)
+            .push(origin.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Read the token from this header, Default: `x-csrf-token`
+    pub fn header(mut self, header: &str) -> Self {
+        self.header = header.to_ascii_lowercase();
+        self
+    }
+
+    /// Read the token from this form field, Default: `csrf_token`
+    pub fn field(mut self, field: &str) -> Self {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// The max size of a form body read for the token, Default: 1 MiB
+    pub fn body_limit(mut self, body_limit: usize) -> Self {
+        self.body_limit = body_limit;
+        self
+    }
+
+    pub(crate) fn is_exempt(&self, path: &str) -> bool {
+        self.exempt
+            .iter()
+            .any(|exempt| match exempt.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == exempt,
+            })
+    }
+
+    pub(crate) fn is_safe(method: &str) -> bool {
+        matches!(method, "GET" | "HEAD" | "OPTIONS" | "TRACE")
+    }
+
+    pub(crate) fn is_form(content_type: Option<&str>) -> bool {
+        content_type.is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"))
+    }
+
+    pub(crate) fn token_from_form(&self, body: &[u8]) -> Option<String> {
+        let body = std::str::from_utf8(body).ok()?;
+        body.split('&').find_map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            if k == self.field {
+                urlencoding::decode(&v.replace('+', " "))
+                    .ok()
+                    .map(|v| v.into_owned())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The `Origin`, or the origin of the `Referer`, is the host or trusted.
+    pub(crate) fn is_same_origin(
+        &self,
+        origin: Option<&str>,
+        referer: Option<&str>,
+        host: Option<&str>,
+    ) -> bool {
+        let origin = match origin.filter(|o| *o != "null").or(referer) {
+            Some(origin) => origin,
+            None => return false,
+        };
+        let (scheme, rest) = match origin.split_once("://") {
+            Some(v) => v,
+            None => return false,
+        };
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let origin = format!("{}://{}", scheme, authority);
+        host.is_some_and(|host| host.eq_ignore_ascii_case(authority))
+            || self
+                .trusted_origins
+                .iter()
+                .any(|o| o.eq_ignore_ascii_case(&origin))
+    }
+
+    /// Check the token sent by the client, a session without token must come
+    /// from a trusted origin.
+    pub(crate) fn is_valid(
+        &self,
+        login_info: &LoginInfo,
+        token: Option<String>,
+        same_origin: bool,
+    ) -> bool {
+        match (token, session_token(login_info)) {
+            (Some(token), Some(expected)) => {
+                bool::from(token.as_bytes().ct_eq(expected.as_bytes()))
+            }
+            (None, Some(_)) => false,
+            (_, None) => same_origin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+
+    fn login_info(token: Option<&str>) -> LoginInfo {
+        let login_info = LoginInfo::default();
+        let mut data = Map::new();
+        if let Some(token) = token {
+            data.insert(CSRF_KEY.to_owned(), token.into());
+        }
+        login_info.set_data(data);
+        login_info
+    }
+
+    #[test]
+    fn token_and_origin() {
+        let csrf = CsrfProtection::new();
+        let with_token = login_info(Some("abc"));
+        let without_token = login_info(None);
+        let sent = |token: &str| Some(token.to_owned());
+        for same_origin in [true, false] {
+            assert!(csrf.is_valid(&with_token, sent("abc"), same_origin));
+            assert!(!csrf.is_valid(&with_token, sent("xyz"), same_origin));
+            // the session has a token, the origin is not enough
+            assert!(!csrf.is_valid(&with_token, None, same_origin));
+            assert_eq!(
+                csrf.is_valid(&without_token, sent("abc"), same_origin),
+                same_origin
+            );
+            assert_eq!(
+                csrf.is_valid(&without_token, None, same_origin),
+                same_origin
+            );
+        }
+    }
+
+    #[test]
+    fn same_origin() {
+        let csrf = CsrfProtection::new().trusted_origin("https://app.example.com/");
+        let host = Some("example.com");
+        assert!(csrf.is_same_origin(Some("https://example.com"), None, host));
+        assert!(csrf.is_same_origin(None, Some("https://example.com/a?b"), host));
+        assert!(csrf.is_same_origin(Some("https://app.example.com"), None, host));
+        assert!(!csrf.is_same_origin(Some("https://evil.com"), None, host));
+        assert!(!csrf.is_same_origin(Some("null"), None, host));
+        assert!(!csrf.is_same_origin(None, None, host));
+    }
+}
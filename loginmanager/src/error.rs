@@ -0,0 +1,214 @@
+use std::{error::Error, fmt, sync::Arc};
+
+#[cfg(feature = "axum_layer")]
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use http::StatusCode;
+use serde_json::Value;
+
+/// The rejection of the extractors and `LoginRequired`.
+///
+/// Rendered as plain text by default, use `LoginManager::problem_json` or
+/// `LoginManager::error_renderer` to change it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginError {
+    /// The `LoginManager` middleware is not used, 500.
+    MissingMiddleware,
+    /// With actix the `web::Data` of `UserMinix::State` is not added to the app, 500.
+    MissingState,
+    /// No user is logged in, 401.
+    NotLoggedIn,
+    /// The user is not active, 401.
+    Inactive,
+    /// The user is not authenticated, 401.
+    Unauthenticated,
+    /// The login is not fresh, see `FreshUser`, 401.
+    NotFresh,
+    /// The user lacks the permission or role, 403.
+    Forbidden,
+    /// The CSRF token is missing or incorrect, see `CsrfProtection`, 403.
+    Csrf,
+    /// The user can not be loaded, see `UserMinix::load_user`, 500 or 503.
+    LoaderFailure(LoaderError),
+}
+
+impl LoginError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MissingMiddleware | Self::MissingState => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::LoaderFailure(err) => err.status(),
+            Self::NotLoggedIn | Self::Inactive | Self::Unauthenticated | Self::NotFresh => {
+                StatusCode::UNAUTHORIZED
+            }
+            Self::Forbidden | Self::Csrf => StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// The RFC 7807 problem details, served as `application/problem+json`.
+    pub fn problem(&self) -> Value {
+        let status = self.status();
+        serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "detail": self.to_string(),
+        })
+    }
+
+    #[cfg(feature = "axum_layer")]
+    pub(crate) fn problem_response(&self) -> Response {
+        (
+            self.status(),
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            self.problem().to_string(),
+        )
+            .into_response()
+    }
+
+    #[cfg(feature = "actix_layer")]
+    pub(crate) fn problem_response2(&self) -> actix_web::HttpResponse {
+        actix_web::HttpResponse::build(actix_web::ResponseError::status_code(self))
+            .content_type("application/problem+json")
+            .body(self.problem().to_string())
+    }
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingMiddleware => "please use loginmanger middleware first",
+            Self::MissingState => "please add the state of the user first",
+            Self::NotLoggedIn => "No authentication.",
+            Self::Inactive => "Inactive user.",
+            Self::Unauthenticated => "No authentication.",
+            Self::NotFresh => "Fresh login required.",
+            Self::Forbidden => "Permission denied.",
+            Self::Csrf => "CSRF token missing or incorrect.",
+            Self::LoaderFailure(_) => "Failed to load user.",
+        })
+    }
+}
+
+impl Error for LoginError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::LoaderFailure(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The failure of `UserMinix::load_user`, convert an error with
+/// `.map_err(LoaderError::new)?` as 500.
+///
+/// Errors compare by their status and message.
+///
+/// ## Example
+/// ```
+/// use loginmanager::LoaderError;
+///
+/// let err = LoaderError::unavailable("database is down");
+/// assert_eq!(err.status(), 503);
+/// assert_eq!(err, LoaderError::unavailable("database is down"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoaderError {
+    status: StatusCode,
+    inner: Arc<dyn Error + Send + Sync>,
+}
+
+impl LoaderError {
+    /// Answered with `500 Internal Server Error`.
+    pub fn new<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            inner: Arc::from(err.into()),
+        }
+    }
+
+    /// Answered with `503 Service Unavailable`, e.g. the database is down.
+    pub fn unavailable<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            inner: Arc::from(err.into()),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The error of the loader.
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.inner
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for LoaderError {
+    fn from(err: Box<dyn Error + Send + Sync>) -> Self {
+        Self::new(err)
+    }
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// The message of the inner error is the message of this one.
+impl Error for LoaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl PartialEq for LoaderError {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status && self.inner.to_string() == other.inner.to_string()
+    }
+}
+
+impl Eq for LoaderError {}
+
+/// The error is kept in the extensions, the `LoginManager` renders it again
+/// with its `error_renderer`.
+#[cfg(feature = "axum_layer")]
+impl IntoResponse for LoginError {
+    fn into_response(self) -> Response {
+        let mut res = (self.status(), self.to_string()).into_response();
+        res.extensions_mut().insert(self);
+        res
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+impl actix_web::ResponseError for LoginError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::from_u16(self.status().as_u16()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_and_source() {
+        assert_eq!(LoginError::NotLoggedIn, LoginError::NotLoggedIn);
+        assert_ne!(LoginError::NotLoggedIn, LoginError::Forbidden);
+        let failure = |err: LoaderError| LoginError::LoaderFailure(err);
+        assert_eq!(
+            failure(LoaderError::new("down")),
+            failure(LoaderError::new("down"))
+        );
+        assert_ne!(
+            failure(LoaderError::new("down")),
+            failure(LoaderError::unavailable("down"))
+        );
+        let err = failure(LoaderError::new("down"));
+        assert_eq!(err.source().unwrap().to_string(), "down");
+    }
+}
@@ -1,33 +1,39 @@
-use futures_util::future::{BoxFuture, LocalBoxFuture};
-use serde::{de::DeserializeOwned, Serialize};
+use std::{any::Any, marker::PhantomData};
 
-use crate::loginmanager::LoginInfo;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 
+use crate::{csrf, loginmanager::LoginInfo, LoaderError, LoginError};
 
+/// The user of `CurrentUser`, `AuthUser`, ... for both axum and actix.
+///
+/// `load_user`, or the `LoginManager::user_loader` registered instead of it,
+/// gets the `State` of the app, e.g. a database connection. With axum it is
+/// taken from the router state with `FromRef`, so the extractors do not
+/// compile without it, a `State` of `()` on a router with state needs
+/// `impl FromRef<AppState> for ()`. With actix add it with
+/// `web::Data::new(state)`, a `State` of `()` needs nothing.
+///
 /// ## Example
 /// ``` no_run
-/// #[async_trait]
-/// impl loginmanager::UserMinix<axum::http::request::Parts> for User {
-///     type Key = i32;
+/// use async_trait::async_trait;
+/// use loginmanager::LoaderError;
+/// # struct Db;
 ///
-///     async fn get_user(id: &Self::Key, req: &mut axum::http::request::Parts) -> Option<Self> {
-///         let user = todo!();
-///         Some(user)
-///     }
-///
-///     fn get_id(&self) -> &Self::Key {
-///         &self.id
-///     }
+/// #[derive(Clone)]
+/// struct User {
+///     id: i32,
 /// }
 ///
-/// use actix_web::HttpRequest;
-/// #[async_trait(?Send)]
-/// impl loginmanager::UserMinix<HttpRequest> for User {
+/// #[async_trait]
+/// impl loginmanager::UserMinix for User {
 ///     type Key = i32;
+///     type State = Db;
 ///
-///     async fn get_user2(id: &Self::Key, req: &mut HttpRequest) -> Option<Self>{
+///     async fn load_user(id: &Self::Key, db: &Self::State) -> Result<Option<Self>, LoaderError> {
 ///         let user = todo!();
-///         Some(user)
+///         Ok(Some(user))
 ///     }
 ///
 ///     fn get_id(&self) -> &Self::Key {
@@ -35,35 +41,54 @@ use crate::loginmanager::LoginInfo;
 ///     }
 /// }
 /// ```
+///
+/// A router without the `State` does not compile:
+/// ``` compile_fail
+/// # use axum::{routing::get, Router};
+/// # use loginmanager::{AuthUser, LoaderError, UserMinix};
+/// # #[derive(Clone)]
+/// # struct Db;
+/// # #[derive(Clone)]
+/// # struct User {
+/// #     id: i32,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl UserMinix for User {
+/// #     type Key = i32;
+/// #     type State = Db;
+/// #     async fn load_user(id: &i32, _: &Db) -> Result<Option<Self>, LoaderError> {
+/// #         Ok(Some(User { id: *id }))
+/// #     }
+/// #     fn get_id(&self) -> &i32 {
+/// #         &self.id
+/// #     }
+/// # }
+/// async fn me(AuthUser(user): AuthUser<User>) {}
+///
+/// // missing `.with_state(Db)`
+/// let app: Router = Router::new().route("/me", get(me));
+/// ```
 #[allow(unused)]
-pub trait UserMinix<R>: Sized + Sync + Send + Clone {
+#[async_trait]
+pub trait UserMinix: Sized + Sync + Send + Clone + 'static {
     /// The type of User, must be same as Loginmanager.
     /// Otherwise no user will be returned.
-    type Key: Serialize + DeserializeOwned + Send + Sync;
+    type Key: Serialize + DeserializeOwned + Send + Sync + 'static;
+
+    /// The state needed by `load_user`.
+    type State: Send + Sync + 'static;
 
-    /// Get user from id and req.
+    /// Get user from id, `Ok(None)` if there is no such user.
     ///
-    /// **Tip**:can use req.app_data to obtain
-    /// database connection defined in Web app.
-    fn get_user<'l1, 'l2, 'a>(id: &'l1 Self::Key, req: &'l2 mut R) -> BoxFuture<'a, Option<Self>>
-    where
-        'l1: 'a,
-        'l2: 'a,
-        Self: 'a,
-    {
-        Box::pin(async { None })
-    }
-
-    fn get_user2<'l1, 'l2, 'a>(
-        id: &'l1 Self::Key,
-        req: &'l2 mut R,
-    ) -> LocalBoxFuture<'a, Option<Self>>
-    where
-        'l1: 'a,
-        'l2: 'a,
-        Self: 'a,
-    {
-        Box::pin(async { None })
+    /// An `Err` (e.g. the database is down) rejects the request with the
+    /// status of the [`LoaderError`] instead of 401.
+    ///
+    /// Leave it out when a `LoginManager::user_loader` is registered for the
+    /// user, without both every request with a user is answered with 500.
+    async fn load_user(id: &Self::Key, state: &Self::State) -> Result<Option<Self>, LoaderError> {
+        Err(LoaderError::new(
+            "implement UserMinix::load_user or register LoginManager::user_loader",
+        ))
     }
 
     /// Return the User id
@@ -78,8 +103,62 @@ pub trait UserMinix<R>: Sized + Sync + Send + Clone {
     fn is_actived(&self) -> bool {
         true
     }
+
+    /// A value kept in the session by `AuthContext::login`, e.g. a counter or
+    /// a hash of the password. The sessions made with another value are logged
+    /// out, so changing it logs the user out of all devices. Default None.
+    ///
+    /// **Tip**: only `CookieSession`, `StoreSession` and `RememberMe` keep it,
+    /// the others (e.g. `JwtBearer`) are not checked. With a `UserCache` it is
+    /// checked against the cached user, call `UserCache::invalidate` after
+    /// changing it or the devices are logged out after the ttl.
+    fn session_epoch(&self) -> Option<String> {
+        None
+    }
 }
 
+/// The session is logged out if the `session_epoch` of the user has changed.
+pub(crate) fn check_epoch<U: UserMinix>(info: &LoginInfo, user: &U) -> bool {
+    let valid = match (info.epoch(), user.session_epoch()) {
+        // `None` for a session made before the user had an epoch
+        (Some(session_epoch), Some(epoch)) => session_epoch == Some(epoch),
+        // the key is not read from a session, e.g. `JwtBearer`
+        _ => true,
+    };
+    if !valid {
+        info.logout();
+    }
+    valid
+}
+
+/// Read the user key of the `LoginInfo`, a key kept as a json string is read
+/// too, e.g. the `sub` of `JwtBearer` is `"1"` for the key `1`.
+pub(crate) fn parse_key<K: DeserializeOwned>(key_str: &str) -> Option<K> {
+    serde_json::from_str(key_str).ok().or_else(|| {
+        let key = serde_json::from_str::<String>(key_str).ok()?;
+        serde_json::from_str(&key).ok()
+    })
+}
+
+/// Marks the request the `request_loader` ran for.
+#[derive(Clone)]
+pub(crate) struct RequestLoaded;
+
+/// The `State` of `()` is always there.
+pub(crate) fn unit_state<'a, S: 'static>() -> Option<&'a S> {
+    (&() as &dyn Any).downcast_ref::<S>()
+}
+
+/// An `AuthUser` must be active and authenticated.
+pub(crate) fn check_active<U: UserMinix>(user: &U) -> Result<(), LoginError> {
+    if !user.is_actived() {
+        Err(LoginError::Inactive)
+    } else if !user.is_authenticated() {
+        Err(LoginError::Unauthenticated)
+    } else {
+        Ok(())
+    }
+}
 
 /// `CurrentUser<T>`
 ///
@@ -109,20 +188,94 @@ impl<T> From<T> for CurrentUser<T> {
 #[derive(Debug, Clone)]
 pub struct AuthUser<T>(pub T);
 
+/// `FreshUser<T>` Extractor
+///
+/// Same as `AuthUser<T>`, and the request will be rejected if the user did not
+/// enter the credentials within `LoginManager::fresh_for`, e.g. the login was
+/// restored by `RememberMe`. The `LoginManager` redirects it to `refresh_view`.
+#[derive(Debug, Clone)]
+pub struct FreshUser<T>(pub T);
+
+/// The roles and permissions of a user.
+#[allow(unused)]
+pub trait Permissions {
+    /// return true if the user is granted the permission.
+    fn has_permission(&self, permission: &str) -> bool;
+
+    /// return true if the user has the role, default False.
+    fn has_role(&self, role: &str) -> bool {
+        false
+    }
+}
+
+/// A permission required by [`RequirePermission`].
+///
+/// ## Example
+/// ```
+/// struct EditPost;
+///
+/// impl loginmanager::Permission for EditPost {
+///     const NAME: &'static str = "post:edit";
+/// }
+/// ```
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+/// A role required by [`RequireRole`].
+pub trait Role {
+    const NAME: &'static str;
+}
+
+/// `RequirePermission<T, P>` Extractor
+///
+/// Same as `AuthUser<T>`, and the request will be rejected with 403
+/// if the user has not the permission `P`.
+pub struct RequirePermission<T, P>(pub T, pub PhantomData<P>);
+
+/// `RequireRole<T, R>` Extractor
+///
+/// Same as `AuthUser<T>`, and the request will be rejected with 403
+/// if the user has not the role `R`.
+pub struct RequireRole<T, R>(pub T, pub PhantomData<R>);
+
+/// Options of [`AuthContext::login_with`].
+#[derive(Debug, Clone, Default)]
+pub struct LoginOptions {
+    /// Keep the user logged in after the session cookie is gone,
+    /// requires the decoder to be wrapped in `RememberMe`.
+    pub remember: bool,
+}
+
 #[derive(Debug)]
 pub struct AuthContext(pub(crate) LoginInfo);
 
 impl AuthContext {
-    pub fn login<U, R>(&mut self, user: &U)
-    where
-        U: UserMinix<R>,
-    {
+    pub fn login<U: UserMinix>(&mut self, user: &U) {
+        self.login_with(user, LoginOptions::default());
+    }
+
+    pub fn login_with<U: UserMinix>(&mut self, user: &U, options: LoginOptions) {
         let key_str = serde_json::to_string(&user.get_id()).ok();
         self.0.login(key_str.unwrap());
+        self.0.set_remember(options.remember);
+        self.0.set_epoch(Some(user.session_epoch()));
+        csrf::rotate_token(&self.0);
     }
 
     pub fn logout(&mut self) {
         self.0.logout();
+        csrf::rotate_token(&self.0);
+    }
+
+    /// Issue a new session identifier and drop the old one,
+    /// `login` always does it.
+    ///
+    /// **Tip**: a `CookieSession` cookie carries no server state, the old
+    /// cookie is replaced in the browser but can not be revoked.
+    /// Use `StoreSession` to invalidate it.
+    pub fn regenerate(&mut self) {
+        self.0.regenerate();
     }
 }
 
@@ -131,3 +284,60 @@ impl From<&LoginInfo> for AuthContext {
         Self(value.clone())
     }
 }
+
+/// `SessionData` Extractor
+///
+/// Small typed values kept in the session alongside the user key, e.g. the
+/// selected tenant or locale. They are written by the decoder only when
+/// modified, and kept after `AuthContext::logout`.
+///
+/// ## Example
+/// ```
+/// use loginmanager::SessionData;
+///
+/// async fn set_locale(mut data: SessionData) {
+///     data.insert("locale", &"en").unwrap();
+///     let locale: Option<String> = data.get("locale");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionData(pub(crate) LoginInfo);
+
+impl SessionData {
+    /// Get the value of `key`, `None` if it is missing or not a `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.0
+            .with_data(|data| data.get(key).cloned())
+            .and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.with_data(|data| data.contains_key(key))
+    }
+
+    /// Set the value of `key`.
+    pub fn insert<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        self.0
+            .update_data(|data| data.insert(key.to_owned(), value));
+        Ok(())
+    }
+
+    /// Remove `key` and return its value.
+    pub fn remove<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        self.0
+            .update_data(|data| data.remove(key))
+            .and_then(|value: Value| serde_json::from_value(value).ok())
+    }
+
+    /// Remove all values.
+    pub fn clear(&mut self) {
+        self.0.update_data(|data| data.clear());
+    }
+}
+
+impl From<&LoginInfo> for SessionData {
+    fn from(value: &LoginInfo) -> Self {
+        Self(value.clone())
+    }
+}
@@ -1,10 +1,34 @@
-use crate::{loginmanager::LoginInfo, AuthContext, AuthUser, CurrentUser, UserMinix};
-use actix_web::{error::InternalError, Error, HttpMessage};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{
+    extractors::{check_active, check_epoch, parse_key, unit_state, RequestLoaded},
+    loginmanager::{Config, LoginInfo},
+    AuthContext, AuthUser, CurrentUser, FreshUser, LoginError, Permission, Permissions,
+    RequirePermission, RequireRole, Role, SessionData, UserMinix,
+};
+use actix_web::{web, Error, HttpMessage};
 use futures_util::future::LocalBoxFuture;
 
+/// The user of the `request_loader2`, it runs once for the request.
+async fn request_user<T: UserMinix>(req: &actix_web::HttpRequest) -> Result<Option<T>, LoginError> {
+    let loader = match req.extensions().get::<Arc<Config>>() {
+        Some(config) => config.request_loader2.clone(),
+        None => None,
+    };
+    let loader = match loader {
+        Some(loader) if req.extensions_mut().insert(RequestLoaded).is_none() => loader,
+        _ => return Ok(None),
+    };
+    // the user is kept in the extensions, not in the session
+    if let Some(extensions) = loader(req).await.map_err(LoginError::LoaderFailure)? {
+        req.extensions_mut().extend(extensions);
+    }
+    Ok(req.extensions().get::<T>().cloned())
+}
+
 impl<T> actix_web::FromRequest for CurrentUser<Option<T>>
 where
-    T: UserMinix<actix_web::HttpRequest> + Clone + Send + Sync + 'static,
+    T: UserMinix,
 {
     type Error = Error;
 
@@ -12,29 +36,47 @@ where
 
     fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
         let req_clone = req.clone();
-        let mut req_clone2 = req.clone();
         Box::pin(async move {
-            let extensions = &mut req_clone.extensions_mut();
-            if let Some(u) = extensions.get::<T>() {
+            if let Some(u) = req_clone.extensions().get::<T>() {
                 return Ok(Self(Some(u.to_owned())));
             }
-            if let Some(info) = extensions.get::<LoginInfo>() {
-                if let Some(key) = info.get_key() {
-                    if let Ok(key) = serde_json::from_str::<T::Key>(&key) {
-                        let real_user = T::get_user2(&key, &mut req_clone2).await;
-                        if let Some(u) = real_user {
-                            extensions.insert(u.to_owned());
+            let info = req_clone.extensions().get::<LoginInfo>().cloned();
+            if let Some(info) = info {
+                if info.get_key().is_none() {
+                    return Ok(Self(request_user(&req_clone).await?));
+                }
+                if let Some(key_str) = info.get_key() {
+                    if let Some(key) = parse_key::<T::Key>(&key_str) {
+                        // the entry `UserCache::invalidate` drops
+                        let key_str = serde_json::to_string(&key).unwrap_or(key_str);
+                        let config = req_clone.extensions().get::<Arc<Config>>().cloned();
+                        let load = async {
+                            let data = req_clone.app_data::<web::Data<T::State>>().cloned();
+                            let state = data
+                                .as_ref()
+                                .map(|data| data.get_ref())
+                                .or_else(unit_state)
+                                .ok_or(LoginError::MissingState)?;
+                            match config.as_deref().and_then(Config::user_loader::<T>) {
+                                Some(loader) => loader.load(key, state).await,
+                                None => T::load_user(&key, state).await,
+                            }
+                            .map_err(LoginError::LoaderFailure)
+                        };
+                        let real_user = match config.as_deref().and_then(|c| c.user_cache.as_ref())
+                        {
+                            Some(cache) => cache.get_or_load(&key_str, load).await?,
+                            None => load.await?,
+                        };
+                        if let Some(u) = real_user.filter(|user| check_epoch(&info, user)) {
+                            req_clone.extensions_mut().insert(u.to_owned());
                             return Ok(Self(Some(u.to_owned())));
                         }
                     }
                 }
                 Ok(Self(None))
             } else {
-                Err(InternalError::new(
-                    "please use loginmanger middleware first",
-                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                )
-                .into())
+                Err(LoginError::MissingMiddleware.into())
             }
         })
     }
@@ -42,7 +84,7 @@ where
 
 impl<T> actix_web::FromRequest for CurrentUser<T>
 where
-    T: UserMinix<actix_web::HttpRequest> + Clone + Send + Sync + 'static,
+    T: UserMinix,
 {
     type Error = Error;
 
@@ -54,20 +96,17 @@ where
     ) -> Self::Future {
         let fut = CurrentUser::<Option<T>>::from_request(req, payload);
         Box::pin(async move {
-            fut.await?.0.map(Into::into).ok_or(
-                InternalError::new(
-                    "No authentication.",
-                    actix_web::http::StatusCode::UNAUTHORIZED,
-                )
-                .into(),
-            )
+            fut.await?
+                .0
+                .map(Into::into)
+                .ok_or(LoginError::NotLoggedIn.into())
         })
     }
 }
 
 impl<T> actix_web::FromRequest for AuthUser<Option<T>>
 where
-    T: UserMinix<actix_web::HttpRequest> + Clone + Send + Sync + 'static,
+    T: UserMinix,
 {
     type Error = Error;
 
@@ -83,15 +122,8 @@ where
             match user {
                 None => Ok(Self(None)),
                 Some(user) => {
-                    if user.is_actived() && user.is_authenticated() {
-                        Ok(Self(Some(user.to_owned())))
-                    } else {
-                        Err(InternalError::new(
-                            "No authentication.",
-                            actix_web::http::StatusCode::UNAUTHORIZED,
-                        )
-                        .into())
-                    }
+                    check_active(&user)?;
+                    Ok(Self(Some(user.to_owned())))
                 }
             }
         })
@@ -100,7 +132,7 @@ where
 
 impl<T> actix_web::FromRequest for AuthUser<T>
 where
-    T: UserMinix<actix_web::HttpRequest> + Clone + Send + Sync + 'static,
+    T: UserMinix,
 {
     type Error = Error;
 
@@ -113,14 +145,89 @@ where
         let f = CurrentUser::<T>::from_request(req, payload);
         Box::pin(async move {
             let user = f.await?.0;
-            if user.is_actived() && user.is_authenticated() {
-                Ok(Self(user.to_owned()))
+            check_active(&user)?;
+            Ok(Self(user.to_owned()))
+        })
+    }
+}
+
+impl<T> actix_web::FromRequest for FreshUser<T>
+where
+    T: UserMinix,
+{
+    type Error = Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let f = AuthUser::<T>::from_request(req, payload);
+        let req = req.clone();
+        Box::pin(async move {
+            let user = f.await?.0;
+            let info = req.extensions().get::<LoginInfo>().cloned();
+            let config = req.extensions().get::<Arc<Config>>().cloned();
+            let (info, config) = match (info, config) {
+                (Some(info), Some(config)) => (info, config),
+                _ => return Err(LoginError::MissingMiddleware.into()),
+            };
+            if info.is_fresh_within(config.fresh_for) {
+                Ok(Self(user))
+            } else {
+                info.set_refresh();
+                Err(LoginError::NotFresh.into())
+            }
+        })
+    }
+}
+
+impl<T, P> actix_web::FromRequest for RequirePermission<T, P>
+where
+    T: UserMinix + Permissions,
+    P: Permission,
+{
+    type Error = Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let f = AuthUser::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let user = f.await?.0;
+            if user.has_permission(P::NAME) {
+                Ok(Self(user, PhantomData))
             } else {
-                Err(InternalError::new(
-                    "No authentication.",
-                    actix_web::http::StatusCode::UNAUTHORIZED,
-                )
-                .into())
+                Err(LoginError::Forbidden.into())
+            }
+        })
+    }
+}
+
+impl<T, R> actix_web::FromRequest for RequireRole<T, R>
+where
+    T: UserMinix + Permissions,
+    R: Role,
+{
+    type Error = Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let f = AuthUser::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let user = f.await?.0;
+            if user.has_role(R::NAME) {
+                Ok(Self(user, PhantomData))
+            } else {
+                Err(LoginError::Forbidden.into())
             }
         })
     }
@@ -134,13 +241,26 @@ impl actix_web::FromRequest for AuthContext {
     fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
         let req = req.clone();
         Box::pin(async move {
-            req.extensions().get::<LoginInfo>().map(Into::into).ok_or(
-                InternalError::new(
-                    "please use loginmanger middleware first",
-                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                )
-                .into(),
-            )
+            req.extensions()
+                .get::<LoginInfo>()
+                .map(Into::into)
+                .ok_or(LoginError::MissingMiddleware.into())
+        })
+    }
+}
+
+impl actix_web::FromRequest for SessionData {
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            req.extensions()
+                .get::<LoginInfo>()
+                .map(Into::into)
+                .ok_or(LoginError::MissingMiddleware.into())
         })
     }
 }
@@ -1,28 +1,70 @@
 use async_trait::async_trait;
 use axum::{
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
 };
 
-use crate::{loginmanager::LoginInfo, AuthContext, AuthUser, CurrentUser, UserMinix};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{
+    extractors::{check_active, check_epoch, parse_key, RequestLoaded},
+    loginmanager::{Config, LoginInfo},
+    AuthContext, AuthUser, CurrentUser, FreshUser, LoginError, Permission, Permissions,
+    RequirePermission, RequireRole, Role, SessionData, UserMinix,
+};
+
+/// The user of the `request_loader`, it runs once for the request.
+async fn request_user<T: UserMinix>(parts: &mut Parts) -> Result<Option<T>, LoginError> {
+    let loader = match parts.extensions.get::<Arc<Config>>() {
+        Some(config) => config.request_loader.clone(),
+        None => None,
+    };
+    let loader = match loader {
+        Some(loader) if parts.extensions.insert(RequestLoaded).is_none() => loader,
+        _ => return Ok(None),
+    };
+    // the user is kept in the extensions, not in the session
+    if let Some(extensions) = loader(parts).await.map_err(LoginError::LoaderFailure)? {
+        parts.extensions.extend(extensions);
+    }
+    Ok(parts.extensions.get::<T>().cloned())
+}
 
 #[async_trait]
 impl<S, T> FromRequestParts<S> for CurrentUser<Option<T>>
 where
-    S: Send + Sync,
-    T: UserMinix<Parts> + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    T: UserMinix,
+    T::State: FromRef<S>,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = LoginError;
 
-    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         if let Some(u) = parts.extensions.get::<T>() {
             return Ok(Self(Some(u.to_owned())));
         }
-        if let Some(info) = parts.extensions.get::<LoginInfo>() {
-            if let Some(key) = info.get_key() {
-                if let Ok(key) = serde_json::from_str::<T::Key>(&key) {
-                    let real_user = T::get_user(&key, parts).await;
-                    if let Some(real_user) = real_user {
+        if let Some(info) = parts.extensions.get::<LoginInfo>().cloned() {
+            if info.get_key().is_none() {
+                return Ok(Self(request_user(parts).await?));
+            }
+            if let Some(key_str) = info.get_key() {
+                if let Some(key) = parse_key::<T::Key>(&key_str) {
+                    // the entry `UserCache::invalidate` drops
+                    let key_str = serde_json::to_string(&key).unwrap_or(key_str);
+                    let config = parts.extensions.get::<Arc<Config>>().cloned();
+                    let load = async {
+                        let state = T::State::from_ref(state);
+                        match config.as_deref().and_then(Config::user_loader::<T>) {
+                            Some(loader) => loader.load(key, &state).await,
+                            None => T::load_user(&key, &state).await,
+                        }
+                        .map_err(LoginError::LoaderFailure)
+                    };
+                    let real_user = match config.as_deref().and_then(|c| c.user_cache.as_ref()) {
+                        Some(cache) => cache.get_or_load(&key_str, load).await?,
+                        None => load.await?,
+                    };
+                    if let Some(real_user) = real_user.filter(|user| check_epoch(&info, user)) {
                         parts.extensions.insert(real_user.to_owned());
                         return Ok(Self(Some(real_user.to_owned())));
                     }
@@ -30,10 +72,7 @@ where
             }
             Ok(Self(None))
         } else {
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "please use loginmanger middleware first",
-            ))
+            Err(LoginError::MissingMiddleware)
         }
     }
 }
@@ -41,27 +80,29 @@ where
 #[async_trait]
 impl<S, T> FromRequestParts<S> for CurrentUser<T>
 where
-    S: Send + Sync,
-    T: UserMinix<Parts> + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    T: UserMinix,
+    T::State: FromRef<S>,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = LoginError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         CurrentUser::<Option<T>>::from_request_parts(parts, state)
             .await?
             .0
             .map(Into::into)
-            .ok_or((StatusCode::UNAUTHORIZED, "No authentication."))
+            .ok_or(LoginError::NotLoggedIn)
     }
 }
 
 #[async_trait]
 impl<S, T> FromRequestParts<S> for AuthUser<Option<T>>
 where
-    S: Send + Sync,
-    T: UserMinix<Parts> + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    T: UserMinix,
+    T::State: FromRef<S>,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = LoginError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let u = CurrentUser::<Option<T>>::from_request_parts(parts, state)
@@ -70,11 +111,8 @@ where
         match u {
             None => Ok(Self(None)),
             Some(u) => {
-                if u.is_actived() && u.is_authenticated() {
-                    Ok(Self(Some(u.to_owned())))
-                } else {
-                    Err((StatusCode::UNAUTHORIZED, "No authentication."))
-                }
+                check_active(&u)?;
+                Ok(Self(Some(u.to_owned())))
             }
         }
     }
@@ -83,17 +121,82 @@ where
 #[async_trait]
 impl<S, T> FromRequestParts<S> for AuthUser<T>
 where
-    S: Send + Sync,
-    T: UserMinix<Parts> + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+    T: UserMinix,
+    T::State: FromRef<S>,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = LoginError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let u = CurrentUser::<T>::from_request_parts(parts, state).await?.0;
-        if u.is_actived() && u.is_authenticated() {
-            Ok(Self(u.to_owned()))
+        check_active(&u)?;
+        Ok(Self(u.to_owned()))
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for FreshUser<T>
+where
+    S: Send + Sync + 'static,
+    T: UserMinix,
+    T::State: FromRef<S>,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let u = AuthUser::<T>::from_request_parts(parts, state).await?.0;
+        let (info, config) = match (
+            parts.extensions.get::<LoginInfo>(),
+            parts.extensions.get::<Arc<Config>>(),
+        ) {
+            (Some(info), Some(config)) => (info, config),
+            _ => return Err(LoginError::MissingMiddleware),
+        };
+        if info.is_fresh_within(config.fresh_for) {
+            Ok(Self(u))
+        } else {
+            info.set_refresh();
+            Err(LoginError::NotFresh)
+        }
+    }
+}
+
+#[async_trait]
+impl<S, T, P> FromRequestParts<S> for RequirePermission<T, P>
+where
+    S: Send + Sync + 'static,
+    T: UserMinix + Permissions,
+    T::State: FromRef<S>,
+    P: Permission,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let u = AuthUser::<T>::from_request_parts(parts, state).await?.0;
+        if u.has_permission(P::NAME) {
+            Ok(Self(u, PhantomData))
+        } else {
+            Err(LoginError::Forbidden)
+        }
+    }
+}
+
+#[async_trait]
+impl<S, T, R> FromRequestParts<S> for RequireRole<T, R>
+where
+    S: Send + Sync + 'static,
+    T: UserMinix + Permissions,
+    T::State: FromRef<S>,
+    R: Role,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let u = AuthUser::<T>::from_request_parts(parts, state).await?.0;
+        if u.has_role(R::NAME) {
+            Ok(Self(u, PhantomData))
         } else {
-            Err((StatusCode::UNAUTHORIZED, "No authentication."))
+            Err(LoginError::Forbidden)
         }
     }
 }
@@ -103,12 +206,29 @@ impl<S> FromRequestParts<S> for AuthContext
 where
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, &'static str);
+    type Rejection = LoginError;
 
     async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
-        parts.extensions.get::<LoginInfo>().map(Into::into).ok_or((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "please use loginmanger middleware first",
-        ))
+        parts
+            .extensions
+            .get::<LoginInfo>()
+            .map(Into::into)
+            .ok_or(LoginError::MissingMiddleware)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SessionData
+where
+    S: Send + Sync,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<LoginInfo>()
+            .map(Into::into)
+            .ok_or(LoginError::MissingMiddleware)
     }
 }
@@ -0,0 +1,143 @@
+#[cfg(feature = "actix_layer")]
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    HttpMessage,
+};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{body::Body, response::Response};
+#[cfg(feature = "axum_layer")]
+use http::Request;
+
+use crate::loginmanager::{DecodeRequest, LoginInfo};
+
+/// Try a tuple of decoders in order, the first one yielding a user key wins.
+///
+/// The position of the matching decoder is recorded in [`LoginInfo::decoder`],
+/// and only that decoder updates the response. When no decoder matched, e.g.
+/// a new login, the first session decoder persists the login (the first
+/// decoder if none keeps a session), the others which do not keep a session
+/// still update the response.
+///
+/// When a later decoder matched, e.g. a `JwtBearer` next to an anonymous
+/// `CookieSession`, the first session decoder still writes the changes of the
+/// `SessionData` (and so `Flash` and the CSRF token) to its anonymous session,
+/// without the user of the other decoder.
+///
+/// ```
+/// use loginmanager::{CookieSession, FirstOf, LoginManager, MemoryStore, StoreSession};
+///
+/// let loginmanager = LoginManager::new(FirstOf((
+///     StoreSession::new(MemoryStore::new()),
+///     CookieSession::new("secret"),
+/// )));
+/// ```
+#[derive(Clone)]
+pub struct FirstOf<T>(pub T);
+
+macro_rules! impl_first_of {
+    ($($idx:tt $name:ident),+) => {
+        #[cfg(feature = "axum_layer")]
+        #[async_trait]
+        impl<$($name),+> DecodeRequest<Request<Body>, Response> for FirstOf<($($name,)+)>
+        where
+            $($name: DecodeRequest<Request<Body>, Response> + Sync,)+
+        {
+            async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+                let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+                $(
+                    if let Some(key) = self.0.$idx.decode(req).await? {
+                        login_info.set_decoder(Some($idx));
+                        return Ok(Some(key));
+                    }
+                )+
+                Ok(None)
+            }
+
+            fn is_session(&self) -> bool {
+                false $(|| <$name as DecodeRequest<Request<Body>, Response>>::is_session(&self.0.$idx))+
+            }
+
+            async fn update(&self, res: &mut Response) {
+                let login_info = res.extensions().get::<LoginInfo>().unwrap().clone();
+                let matched = login_info.decoder();
+                let session = None$(.or_else(|| {
+                    <$name as DecodeRequest<Request<Body>, Response>>::is_session(&self.0.$idx).then_some($idx)
+                }))+;
+                let target = matched.or(session).unwrap_or(0);
+                // the session decoder tried before the matched one keeps its data
+                let anonymous = session.filter(|&session| matched.is_some_and(|m| session < m));
+                $(
+                    if target == $idx
+                        || (matched.is_none()
+                            && !<$name as DecodeRequest<Request<Body>, Response>>::is_session(&self.0.$idx))
+                    {
+                        self.0.$idx.update(res).await;
+                    } else if anonymous == Some($idx) {
+                        login_info.set_data_only(true);
+                        self.0.$idx.update(res).await;
+                        login_info.set_data_only(false);
+                    }
+                )+
+            }
+        }
+
+        #[cfg(feature = "actix_layer")]
+        #[async_trait(?Send)]
+        impl<B, $($name),+> DecodeRequest<ServiceRequest, ServiceResponse<B>> for FirstOf<($($name,)+)>
+        where
+            $($name: DecodeRequest<ServiceRequest, ServiceResponse<B>>,)+
+        {
+            async fn decode2(
+                &self,
+                req: &mut ServiceRequest,
+            ) -> Result<Option<String>, ServiceResponse<B>> {
+                let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+                $(
+                    if let Some(key) = self.0.$idx.decode2(req).await? {
+                        login_info.set_decoder(Some($idx));
+                        return Ok(Some(key));
+                    }
+                )+
+                Ok(None)
+            }
+
+            fn is_session(&self) -> bool {
+                false $(|| <$name as DecodeRequest<ServiceRequest, ServiceResponse<B>>>::is_session(&self.0.$idx))+
+            }
+
+            async fn update2(&self, res: &mut ServiceResponse<B>) {
+                let login_info = res
+                    .request()
+                    .extensions()
+                    .get::<LoginInfo>()
+                    .unwrap()
+                    .clone();
+                let matched = login_info.decoder();
+                let session = None$(.or_else(|| {
+                    <$name as DecodeRequest<ServiceRequest, ServiceResponse<B>>>::is_session(&self.0.$idx).then_some($idx)
+                }))+;
+                let target = matched.or(session).unwrap_or(0);
+                // the session decoder tried before the matched one keeps its data
+                let anonymous = session.filter(|&session| matched.is_some_and(|m| session < m));
+                $(
+                    if target == $idx
+                        || (matched.is_none()
+                            && !<$name as DecodeRequest<ServiceRequest, ServiceResponse<B>>>::is_session(&self.0.$idx))
+                    {
+                        self.0.$idx.update2(res).await;
+                    } else if anonymous == Some($idx) {
+                        login_info.set_data_only(true);
+                        self.0.$idx.update2(res).await;
+                        login_info.set_data_only(false);
+                    }
+                )+
+            }
+        }
+    };
+}
+
+impl_first_of!(0 T0, 1 T1);
+impl_first_of!(0 T0, 1 T1, 2 T2);
+impl_first_of!(0 T0, 1 T1, 2 T2, 3 T3);
+impl_first_of!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4);
@@ -0,0 +1,204 @@
+#[cfg(feature = "actix_layer")]
+use actix_web::HttpMessage;
+#[cfg(feature = "axum_layer")]
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{extract::FromRequestParts, http::request::Parts};
+#[cfg(feature = "actix_layer")]
+use futures_util::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::{loginmanager::LoginInfo, LoginError};
+
+/// The key of the flash messages in the `SessionData`.
+const FLASHES_KEY: &str = "_flashes";
+
+/// The level of a [`FlashMessage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// A message shown once on the next page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    pub level: Level,
+    pub message: String,
+}
+
+/// Queue a message in the session.
+pub(crate) fn push_flash(login_info: &LoginInfo, message: FlashMessage) {
+    login_info.update_data(|data| {
+        let mut flashes: Vec<FlashMessage> = data
+            .get(FLASHES_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default();
+        flashes.push(message);
+        data.insert(
+            FLASHES_KEY.to_owned(),
+            serde_json::to_value(flashes).unwrap(),
+        );
+    });
+}
+
+/// Take the queued messages from the session.
+fn take_flashes(login_info: &LoginInfo) -> Vec<FlashMessage> {
+    // the session is only written again when there were messages
+    if !login_info.with_data(|data| data.contains_key(FLASHES_KEY)) {
+        return vec![];
+    }
+    login_info
+        .update_data(|data| data.remove(FLASHES_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// `Flash` Extractor
+///
+/// Queue messages for the next request, e.g. before a redirect.
+/// They are kept in the session, a decoder writing `SessionData` is required.
+///
+/// ## Example
+/// ```
+/// use loginmanager::Flash;
+///
+/// async fn logout(mut flash: Flash) {
+///     flash.info("You have been logged out.");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Flash(LoginInfo);
+
+impl Flash {
+    pub fn push<S: Into<String>>(&mut self, level: Level, message: S) {
+        push_flash(
+            &self.0,
+            FlashMessage {
+                level,
+                message: message.into(),
+            },
+        );
+    }
+
+    pub fn debug<S: Into<String>>(&mut self, message: S) {
+        self.push(Level::Debug, message);
+    }
+
+    pub fn info<S: Into<String>>(&mut self, message: S) {
+        self.push(Level::Info, message);
+    }
+
+    pub fn success<S: Into<String>>(&mut self, message: S) {
+        self.push(Level::Success, message);
+    }
+
+    pub fn warning<S: Into<String>>(&mut self, message: S) {
+        self.push(Level::Warning, message);
+    }
+
+    pub fn error<S: Into<String>>(&mut self, message: S) {
+        self.push(Level::Error, message);
+    }
+}
+
+/// `IncomingFlashes` Extractor
+///
+/// The messages queued by [`Flash`], they are removed from the session.
+#[derive(Debug, Clone, Default)]
+pub struct IncomingFlashes(pub Vec<FlashMessage>);
+
+impl IncomingFlashes {
+    pub fn iter(&self) -> std::slice::Iter<'_, FlashMessage> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl IntoIterator for IncomingFlashes {
+    type Item = FlashMessage;
+    type IntoIter = std::vec::IntoIter<FlashMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<S> FromRequestParts<S> for Flash
+where
+    S: Send + Sync,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<LoginInfo>()
+            .map(|info| Self(info.clone()))
+            .ok_or(LoginError::MissingMiddleware)
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<S> FromRequestParts<S> for IncomingFlashes
+where
+    S: Send + Sync,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<LoginInfo>()
+            .map(|info| Self(take_flashes(info)))
+            .ok_or(LoginError::MissingMiddleware)
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+impl actix_web::FromRequest for Flash {
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            req.extensions()
+                .get::<LoginInfo>()
+                .map(|info| Self(info.clone()))
+                .ok_or(LoginError::MissingMiddleware.into())
+        })
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+impl actix_web::FromRequest for IncomingFlashes {
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            req.extensions()
+                .get::<LoginInfo>()
+                .map(|info| Self(take_flashes(info)))
+                .ok_or(LoginError::MissingMiddleware.into())
+        })
+    }
+}
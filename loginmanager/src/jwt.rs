@@ -0,0 +1,266 @@
+#[cfg(feature = "actix_layer")]
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{body::Body, response::Response};
+#[cfg(feature = "axum_layer")]
+use http::{header, Request};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde_json::{Map, Value};
+use time::{Duration, OffsetDateTime};
+
+use crate::{loginmanager::DecodeRequest, AuthContext};
+
+/// The error of signing or verifying a token.
+pub type JwtError = jsonwebtoken::errors::Error;
+
+/// Decode the user key from the `Authorization: Bearer <token>` header.
+///
+/// The user key is kept in the claim `sub` as a string (RFC 7519), e.g.
+/// `{"sub": "1"}` for the key `1_i32`, so tokens issued by other services work
+/// too. Tokens with a bad signature, expired `exp`, future `nbf` or a
+/// mismatched `iss`/`aud` are treated as not logged in.
+#[derive(Clone)]
+pub struct JwtBearer {
+    algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+    claim: String,
+    issuer: Option<String>,
+    audience: Option<String>,
+    expires_in: Duration,
+    leeway: u64,
+}
+
+impl JwtBearer {
+    /// Use `encoding` to mint tokens and `decoding` to verify them.
+    /// Without `encoding` tokens can only be verified.
+    pub fn new(algorithm: Algorithm, decoding: DecodingKey, encoding: Option<EncodingKey>) -> Self {
+        Self {
+            algorithm,
+            encoding,
+            decoding,
+            claim: "sub".to_owned(),
+            issuer: None,
+            audience: None,
+            expires_in: Duration::hours(1),
+            leeway: 60,
+        }
+    }
+
+    /// HS256 with a shared secret.
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self::new(
+            Algorithm::HS256,
+            DecodingKey::from_secret(secret),
+            Some(EncodingKey::from_secret(secret)),
+        )
+    }
+
+    /// RS256 with PEM encoded keys, the private key is only needed to mint tokens.
+    pub fn rs256(public_pem: &[u8], private_pem: Option<&[u8]>) -> Result<Self, JwtError> {
+        let encoding = private_pem.map(EncodingKey::from_rsa_pem).transpose()?;
+        Ok(Self::new(
+            Algorithm::RS256,
+            DecodingKey::from_rsa_pem(public_pem)?,
+            encoding,
+        ))
+    }
+
+    /// EdDSA (Ed25519) with PEM encoded keys, the private key is only needed to mint tokens.
+    pub fn eddsa(public_pem: &[u8], private_pem: Option<&[u8]>) -> Result<Self, JwtError> {
+        let encoding = private_pem.map(EncodingKey::from_ed_pem).transpose()?;
+        Ok(Self::new(
+            Algorithm::EdDSA,
+            DecodingKey::from_ed_pem(public_pem)?,
+            encoding,
+        ))
+    }
+
+    /// The claim carrying the user key, Default: `sub`
+    pub fn claim(mut self, claim: &str) -> Self {
+        self.claim = claim.to_owned();
+        self
+    }
+
+    /// Required `iss`, Default: not checked
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_owned());
+        self
+    }
+
+    /// Required `aud`, Default: not checked
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_owned());
+        self
+    }
+
+    /// Lifetime of minted tokens, Default: 1 hour
+    pub fn expires_in(mut self, expires_in: Duration) -> Self {
+        self.expires_in = expires_in;
+        self
+    }
+
+    /// Clock skew allowed for `exp` and `nbf` in seconds, Default: 60
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Mint a token for the serialized user key.
+    pub fn mint(&self, key_str: &str) -> Result<String, JwtError> {
+        let encoding = self
+            .encoding
+            .as_ref()
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)?;
+        // `sub` is a StringOrURI, `"1"` for the key `1`
+        let key = match serde_json::from_str(key_str)? {
+            Value::String(key) => key,
+            key => key.to_string(),
+        };
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut claims = Map::new();
+        claims.insert(self.claim.clone(), key.into());
+        claims.insert("iat".to_owned(), now.into());
+        claims.insert("nbf".to_owned(), now.into());
+        claims.insert(
+            "exp".to_owned(),
+            (now + self.expires_in.whole_seconds()).into(),
+        );
+        if let Some(ref issuer) = self.issuer {
+            claims.insert("iss".to_owned(), issuer.clone().into());
+        }
+        if let Some(ref audience) = self.audience {
+            claims.insert("aud".to_owned(), audience.clone().into());
+        }
+        jsonwebtoken::encode(&Header::new(self.algorithm), &claims, encoding)
+    }
+
+    /// Mint a token for the user logged in by [`AuthContext::login`],
+    /// `None` if no user logged in this request.
+    pub fn mint_login(&self, auth_context: &AuthContext) -> Result<Option<String>, JwtError> {
+        auth_context
+            .0
+            .login_key()
+            .map(|key| self.mint(&key))
+            .transpose()
+    }
+
+    fn validation(&self) -> Validation {
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = self.leeway;
+        validation.validate_nbf = true;
+        if let Some(ref issuer) = self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match self.audience {
+            Some(ref audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        validation
+    }
+
+    /// Verify the `Authorization` header value and get the user key.
+    ///
+    /// The key is the `sub` as a json string, the extractors read `"1"` as
+    /// the key `1` too.
+    fn key_from(&self, authorization: Option<&str>) -> Option<String> {
+        let token = authorization?.strip_prefix("Bearer ")?.trim();
+        let data =
+            jsonwebtoken::decode::<Map<String, Value>>(token, &self.decoding, &self.validation())
+                .ok()?;
+        match data.claims.get(&self.claim)? {
+            Value::String(key) => Some(Value::from(key.as_str()).to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl DecodeRequest<Request<Body>, Response> for JwtBearer {
+    async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+        Ok(self.key_from(authorization))
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+#[async_trait(?Send)]
+impl DecodeRequest<ServiceRequest, ServiceResponse> for JwtBearer {
+    async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
+        let authorization = req
+            .headers()
+            .get(actix_web::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+        Ok(self.key_from(authorization))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::extractors::parse_key;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn now() -> i64 {
+        OffsetDateTime::now_utc().unix_timestamp()
+    }
+
+    fn token(algorithm: Algorithm, claims: Value) -> String {
+        let encoding = EncodingKey::from_secret(SECRET);
+        let token = jsonwebtoken::encode(&Header::new(algorithm), &claims, &encoding).unwrap();
+        format!("Bearer {token}")
+    }
+
+    #[test]
+    fn string_sub() {
+        let jwt = JwtBearer::hs256(SECRET);
+        let minted = format!("Bearer {}", jwt.mint("1").unwrap());
+        let key = jwt.key_from(Some(&minted)).unwrap();
+        assert_eq!(parse_key::<i32>(&key), Some(1));
+        let minted = format!("Bearer {}", jwt.mint(r#""alice""#).unwrap());
+        let key = jwt.key_from(Some(&minted)).unwrap();
+        assert_eq!(parse_key::<String>(&key).as_deref(), Some("alice"));
+
+        // issued by another service
+        let token = token(Algorithm::HS256, json!({"sub": "42", "exp": now() + 60}));
+        let key = jwt.key_from(Some(&token)).unwrap();
+        assert_eq!(parse_key::<i32>(&key), Some(42));
+        assert_eq!(parse_key::<String>(&key).as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn rejected_tokens() {
+        let jwt = JwtBearer::hs256(SECRET).issuer("me").audience("app");
+        let valid = json!({"sub": "1", "exp": now() + 60, "iss": "me", "aud": "app"});
+        assert!(jwt
+            .key_from(Some(&token(Algorithm::HS256, valid.clone())))
+            .is_some());
+
+        let with = |key: &str, value: Value| {
+            let mut claims = valid.clone();
+            claims[key] = value;
+            token(Algorithm::HS256, claims)
+        };
+        for token in [
+            with("exp", json!(now() - 120)),
+            with("nbf", json!(now() + 120)),
+            with("iss", json!("other")),
+            with("aud", json!("other")),
+            with("sub", json!(1)),
+            token(Algorithm::HS384, valid.clone()),
+        ] {
+            assert_eq!(jwt.key_from(Some(&token)), None, "{token}");
+        }
+        let other = JwtBearer::hs256(b"other").issuer("me").audience("app");
+        let token = format!("Bearer {}", other.mint("1").unwrap());
+        assert_eq!(jwt.key_from(Some(&token)), None);
+    }
+}
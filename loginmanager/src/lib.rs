@@ -1,18 +1,50 @@
 #![cfg_attr(not(doctest), doc = include_str!("../README.md"))]
 
+mod api_key;
+mod basic_auth;
 mod cooke_session;
+mod csrf;
+mod error;
 mod extractors;
 #[cfg(feature = "actix_layer")]
 mod extractors_actix;
 #[cfg(feature = "axum_layer")]
 mod extractors_axum;
+mod first_of;
+mod flash;
+#[cfg(feature = "jwt")]
+mod jwt;
 mod loginmanager;
 #[cfg(feature = "actix_layer")]
 mod loginmanager_actix;
 #[cfg(feature = "axum_layer")]
 mod loginmanager_axum;
-// mod loginrequired;
+mod loginrequired;
+mod next_url;
+mod remember;
+mod store;
+mod user_cache;
+mod user_loader;
+pub use api_key::{ApiKey, ApiKeyRecord, ApiKeyStore, HASH_PREFIX_LEN};
+pub use basic_auth::{BasicAuth, BasicVerifier};
 pub use cooke_session::CookieSession;
-pub use extractors::{AuthContext, AuthUser, CurrentUser, UserMinix};
-pub use loginmanager::{DecodeRequest, LoginInfo, LoginManager};
-// pub use loginrequired::LoginRequired;
+pub use csrf::{CsrfProtection, CsrfToken};
+pub use error::{LoaderError, LoginError};
+pub use extractors::{
+    AuthContext, AuthUser, CurrentUser, FreshUser, LoginOptions, Permission, Permissions,
+    RequirePermission, RequireRole, Role, SessionData, UserMinix,
+};
+pub use first_of::FirstOf;
+pub use flash::{Flash, FlashMessage, IncomingFlashes, Level};
+#[cfg(feature = "jwt")]
+pub use jwt::{JwtBearer, JwtError};
+pub use loginmanager::{DecodeRequest, LoginInfo, LoginManager, Unauthorized};
+pub use loginrequired::LoginRequired;
+pub use next_url::NextUrl;
+pub use remember::RememberMe;
+#[cfg(feature = "redis_store")]
+pub use store::RedisStore;
+#[cfg(feature = "sqlite_store")]
+pub use store::SqliteStore;
+pub use store::{MemoryStore, SessionRecord, SessionStore, StoreError, StoreSession};
+pub use user_cache::UserCache;
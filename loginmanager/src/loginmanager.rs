@@ -1,5 +1,18 @@
 use futures::future::{BoxFuture, LocalBoxFuture};
-use std::sync::{Arc, RwLock};
+use serde_json::{Map, Value};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, RwLock},
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    flash::{push_flash, FlashMessage, Level},
+    user_loader::UserLoader,
+    LoaderError, LoginError, UserCache, UserMinix,
+};
 
 #[allow(unused)]
 pub trait DecodeRequest<Req, Res>: Sized + Send {
@@ -51,34 +64,49 @@ pub trait DecodeRequest<Req, Res>: Sized + Send {
     {
         Box::pin(async {})
     }
+
+    /// The decoder keeps a session, i.e. `update` persists a login.
+    ///
+    /// [`crate::FirstOf`] lets only the first of them persist a new login.
+    fn is_session(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct LoginInfoInner {
     pub key_str: Option<String>,
     pub logout: bool,
     pub new_key: Option<String>,
     pub ext: Option<String>,
-}
-
-impl Default for LoginInfoInner {
-    fn default() -> Self {
-        Self {
-            key_str: None,
-            logout: false,
-            new_key: None,
-            ext: None,
-        }
-    }
+    pub session_id: Option<String>,
+    pub decoder: Option<usize>,
+    pub scopes: Vec<String>,
+    pub regenerate: bool,
+    pub remember: bool,
+    pub remember_series: Option<String>,
+    pub remember_cookie: Option<String>,
+    pub login_at: Option<i64>,
+    pub last_seen: Option<i64>,
+    pub fresh: bool,
+    pub refresh: bool,
+    pub epoch: Option<Option<String>>,
+    pub data: Map<String, Value>,
+    pub data_changed: bool,
+    pub data_only: bool,
+    pub accepts_challenge: bool,
 }
 
 impl LoginInfoInner {
     pub fn login(&mut self, key_str: String) {
-        self.new_key = Some(key_str)
+        self.new_key = Some(key_str);
+        self.login_at = Some(OffsetDateTime::now_utc().unix_timestamp());
+        self.fresh = true;
     }
 
     pub fn logout(&mut self) {
         self.key_str = None;
+        self.epoch = None;
         self.logout = true;
     }
 }
@@ -118,6 +146,159 @@ impl LoginInfo {
         self.0.read().unwrap().logout
     }
 
+    /// issue a new session identifier, e.g. after a privilege change.
+    pub fn regenerate(&self) {
+        self.0.write().unwrap().regenerate = true;
+    }
+
+    /// the session identifier is renewed, on login or `regenerate`.
+    pub fn is_regenerate(&self) -> bool {
+        let inner = self.0.read().unwrap();
+        inner.regenerate || inner.new_key.is_some()
+    }
+
+    /// new user login with a remember-me cookie.
+    pub fn is_remember(&self) -> bool {
+        self.0.read().unwrap().remember
+    }
+
+    pub(crate) fn set_remember(&self, remember: bool) {
+        self.0.write().unwrap().remember = remember;
+    }
+
+    /// the remember-me series sent by the client.
+    pub(crate) fn remember_series(&self) -> Option<String> {
+        self.0.read().unwrap().remember_series.clone()
+    }
+
+    pub(crate) fn set_remember_series(&self, series: Option<String>) {
+        self.0.write().unwrap().remember_series = series;
+    }
+
+    /// the rotated remember-me cookie to send back.
+    pub(crate) fn remember_cookie(&self) -> Option<String> {
+        self.0.read().unwrap().remember_cookie.clone()
+    }
+
+    pub(crate) fn set_remember_cookie(&self, value: Option<String>) {
+        self.0.write().unwrap().remember_cookie = value;
+    }
+
+    /// the time the user entered the credentials, kept across requests by the session.
+    pub fn login_at(&self) -> Option<OffsetDateTime> {
+        let login_at = self.0.read().unwrap().login_at?;
+        OffsetDateTime::from_unix_timestamp(login_at).ok()
+    }
+
+    pub(crate) fn login_at_timestamp(&self) -> Option<i64> {
+        self.0.read().unwrap().login_at
+    }
+
+    /// unix time the session was last written.
+    pub(crate) fn last_seen(&self) -> Option<i64> {
+        self.0.read().unwrap().last_seen
+    }
+
+    pub(crate) fn set_last_seen(&self, last_seen: Option<i64>) {
+        self.0.write().unwrap().last_seen = last_seen;
+    }
+
+    /// the login was made with the credentials, not restored by `RememberMe`.
+    pub fn is_fresh(&self) -> bool {
+        self.0.read().unwrap().fresh
+    }
+
+    /// fresh and entered less than `window` ago.
+    pub fn is_fresh_within(&self, window: Duration) -> bool {
+        self.is_fresh()
+            && self
+                .login_at()
+                .is_some_and(|login_at| OffsetDateTime::now_utc() - login_at <= window)
+    }
+
+    pub(crate) fn set_login_at(&self, login_at: Option<i64>) {
+        self.0.write().unwrap().login_at = login_at;
+    }
+
+    pub(crate) fn set_fresh(&self, fresh: bool) {
+        self.0.write().unwrap().fresh = fresh;
+    }
+
+    /// the `UserMinix::session_epoch` the session was made with,
+    /// `None` when the key is not read from a session, e.g. `JwtBearer`.
+    pub(crate) fn epoch(&self) -> Option<Option<String>> {
+        self.0.read().unwrap().epoch.clone()
+    }
+
+    pub(crate) fn set_epoch(&self, epoch: Option<Option<String>>) {
+        self.0.write().unwrap().epoch = epoch;
+    }
+
+    /// a `FreshUser` asked the user to enter the credentials again.
+    pub(crate) fn is_refresh(&self) -> bool {
+        self.0.read().unwrap().refresh
+    }
+
+    pub(crate) fn set_refresh(&self) {
+        self.0.write().unwrap().refresh = true;
+    }
+
+    /// the custom data of the session, see `SessionData`.
+    pub(crate) fn data(&self) -> Map<String, Value> {
+        self.0.read().unwrap().data.clone()
+    }
+
+    pub(crate) fn set_data(&self, data: Map<String, Value>) {
+        self.0.write().unwrap().data = data;
+    }
+
+    pub(crate) fn with_data<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&Map<String, Value>) -> T,
+    {
+        f(&self.0.read().unwrap().data)
+    }
+
+    /// modify the custom data, it will be written by the decoder.
+    pub(crate) fn update_data<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Map<String, Value>) -> T,
+    {
+        let mut inner = self.0.write().unwrap();
+        inner.data_changed = true;
+        f(&mut inner.data)
+    }
+
+    /// the custom data was modified in this request.
+    pub(crate) fn is_data_changed(&self) -> bool {
+        self.0.read().unwrap().data_changed
+    }
+
+    /// the user key is decoded by another decoder of `FirstOf`, the session
+    /// decoder only writes the `SessionData` of its anonymous session.
+    pub(crate) fn is_data_only(&self) -> bool {
+        self.0.read().unwrap().data_only
+    }
+
+    pub(crate) fn set_data_only(&self, data_only: bool) {
+        self.0.write().unwrap().data_only = data_only;
+    }
+
+    /// The user key the session must be written with:
+    /// `None` when the session is unchanged, `Some(None)` after logout.
+    pub(crate) fn pending_key(&self) -> Option<Option<String>> {
+        let inner = self.0.read().unwrap();
+        if inner.logout {
+            Some(None)
+        } else if inner.new_key.is_some() {
+            Some(inner.new_key.clone())
+        } else if inner.regenerate {
+            Some(inner.key_str.clone())
+        } else {
+            None
+        }
+    }
+
     pub fn ext(&self) -> Option<String> {
         self.0.read().unwrap().ext.clone()
     }
@@ -125,22 +306,197 @@ impl LoginInfo {
     pub fn set_ext(&self, ext: Option<String>) {
         self.0.write().unwrap().ext = ext;
     }
+
+    /// the id of the server-side session this request belongs to.
+    pub(crate) fn session_id(&self) -> Option<String> {
+        self.0.read().unwrap().session_id.clone()
+    }
+
+    pub(crate) fn set_session_id(&self, session_id: Option<String>) {
+        self.0.write().unwrap().session_id = session_id;
+    }
+
+    /// the client may answer a `WWW-Authenticate` challenge.
+    ///
+    /// Set when no decoder keeps a session, e.g. an API, or when the request
+    /// sent the credentials of the challenge, see [`crate::BasicAuth`].
+    pub fn accepts_challenge(&self) -> bool {
+        self.0.read().unwrap().accepts_challenge
+    }
+
+    pub(crate) fn set_accepts_challenge(&self, accepts_challenge: bool) {
+        self.0.write().unwrap().accepts_challenge = accepts_challenge;
+    }
+
+    /// the position of the decoder in `FirstOf` which decoded the user key.
+    pub fn decoder(&self) -> Option<usize> {
+        self.0.read().unwrap().decoder
+    }
+
+    pub(crate) fn set_decoder(&self, decoder: Option<usize>) {
+        self.0.write().unwrap().decoder = decoder;
+    }
+
+    /// the scopes granted to the credential, e.g. an `ApiKey`.
+    pub fn scopes(&self) -> Vec<String> {
+        self.0.read().unwrap().scopes.clone()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.0.read().unwrap().scopes.iter().any(|s| s == scope)
+    }
+
+    pub(crate) fn set_scopes(&self, scopes: Vec<String>) {
+        self.0.write().unwrap().scopes = scopes;
+    }
 }
 
-pub(crate) struct Inner<D> {
-    pub(crate) decoder: D,
+/// A request answered with `401 Unauthorized`, see `LoginManager::unauthorized_handler`.
+#[derive(Debug, Clone)]
+pub struct Unauthorized {
+    pub method: String,
+    /// The path and query of the request.
+    pub uri: String,
+    pub accept: Option<String>,
+    /// The request is sent by htmx, `HX-Request: true`.
+    pub htmx: bool,
+    /// The `login_view` (or `refresh_view`) with `?next=` back to `uri`.
+    pub login_url: String,
+}
+
+impl Unauthorized {
+    /// A page load of a browser, `GET` or `HEAD` accepting `text/html`.
+    pub fn is_html(&self) -> bool {
+        matches!(self.method.as_str(), "GET" | "HEAD")
+            && self
+                .accept
+                .as_deref()
+                .is_some_and(|accept| accept.contains("text/html"))
+    }
+
+    /// The body of the default answer to API callers.
+    pub(crate) fn json(&self) -> Value {
+        serde_json::json!({
+            "error": "unauthorized",
+            "login_url": self.login_url,
+        })
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+pub(crate) type UnauthorizedHandler =
+    Arc<dyn Fn(&Unauthorized) -> axum::response::Response + Send + Sync>;
+
+#[cfg(feature = "actix_layer")]
+pub(crate) type UnauthorizedHandler2 =
+    Arc<dyn Fn(&Unauthorized) -> actix_web::HttpResponse + Send + Sync>;
+
+#[cfg(feature = "axum_layer")]
+pub(crate) type RequestLoader = Arc<
+    dyn Fn(
+            &http::request::Parts,
+        ) -> BoxFuture<'static, Result<Option<http::Extensions>, LoaderError>>
+        + Send
+        + Sync,
+>;
+
+#[cfg(feature = "actix_layer")]
+pub(crate) type RequestLoader2 = Arc<
+    dyn Fn(
+            &actix_web::HttpRequest,
+        )
+            -> LocalBoxFuture<'static, Result<Option<actix_web::dev::Extensions>, LoaderError>>
+        + Send
+        + Sync,
+>;
+
+#[cfg(feature = "axum_layer")]
+pub(crate) type ErrorRenderer = Arc<dyn Fn(&LoginError) -> axum::response::Response + Send + Sync>;
+
+#[cfg(feature = "actix_layer")]
+pub(crate) type ErrorRenderer2 = Arc<dyn Fn(&LoginError) -> actix_web::HttpResponse + Send + Sync>;
+
+/// The settings of a `LoginManager` shared with the extractors.
+pub(crate) struct Config {
     pub(crate) login_view: String,
+    pub(crate) refresh_view: Option<String>,
     pub(crate) next_key: String,
+    pub(crate) next_default: String,
     pub(crate) redirect: bool,
+    pub(crate) rejections_only: bool,
+    pub(crate) fresh_for: Duration,
+    pub(crate) login_message: Option<String>,
+    pub(crate) login_message_level: Level,
+    #[cfg(feature = "axum_layer")]
+    pub(crate) unauthorized_handler: Option<UnauthorizedHandler>,
+    #[cfg(feature = "actix_layer")]
+    pub(crate) unauthorized_handler2: Option<UnauthorizedHandler2>,
+    #[cfg(feature = "axum_layer")]
+    pub(crate) error_renderer: Option<ErrorRenderer>,
+    #[cfg(feature = "actix_layer")]
+    pub(crate) error_renderer2: Option<ErrorRenderer2>,
+    /// `UserLoader<U>` by the `TypeId` of `U`.
+    pub(crate) user_loaders: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    pub(crate) user_cache: Option<UserCache>,
+    #[cfg(feature = "axum_layer")]
+    pub(crate) request_loader: Option<RequestLoader>,
+    #[cfg(feature = "actix_layer")]
+    pub(crate) request_loader2: Option<RequestLoader2>,
 }
 
-impl<D> Inner<D> {
+impl Config {
+    /// the closure registered with `LoginManager::user_loader` for `U`.
+    pub(crate) fn user_loader<U: UserMinix>(&self) -> Option<&UserLoader<U>> {
+        self.user_loaders.get(&TypeId::of::<U>())?.downcast_ref()
+    }
+
     /// get next uri
     pub fn next_to(&self, uri: &str) -> String {
         let uri = urlencoding::encode_binary(uri.as_bytes()).into_owned();
         format!("{}?{}={}", self.login_view, self.next_key, uri)
     }
+
+    /// get next uri of the re-authentication page, `login_view` if it is not set.
+    pub fn refresh_to(&self, uri: &str) -> String {
+        match self.refresh_view {
+            Some(ref refresh_view) => {
+                let uri = urlencoding::encode_binary(uri.as_bytes()).into_owned();
+                format!("{}?{}={}", refresh_view, self.next_key, uri)
+            }
+            None => self.next_to(uri),
+        }
+    }
+
+    /// the redirect of an unauthorized request.
+    pub(crate) fn redirect_to(&self, uri: &str, login_info: &LoginInfo) -> String {
+        if login_info.is_refresh() {
+            self.refresh_to(uri)
+        } else {
+            self.next_to(uri)
+        }
+    }
+
+    /// queue the `login_message` before redirecting to `login_view`.
+    pub(crate) fn flash_login_message(&self, login_info: &LoginInfo) {
+        if let Some(ref message) = self.login_message {
+            if !login_info.is_refresh() {
+                push_flash(
+                    login_info,
+                    FlashMessage {
+                        level: self.login_message_level,
+                        message: message.clone(),
+                    },
+                );
+            }
+        }
+    }
 }
+
+pub(crate) struct Inner<D> {
+    pub(crate) decoder: D,
+    pub(crate) config: Arc<Config>,
+}
+
 /// LoginManager<D> is implemented as a middleware.
 ///
 /// - `D` the type of DecodeRequest. It decode the key_string from request.
@@ -151,27 +507,294 @@ impl<D> LoginManager<D> {
     pub fn new(decoder: D) -> Self {
         Self(Arc::new(Inner {
             decoder,
-            login_view: "/login".to_owned(),
-            next_key: "next".to_owned(),
-            redirect: true,
+            config: Arc::new(Config {
+                login_view: "/login".to_owned(),
+                refresh_view: None,
+                next_key: "next".to_owned(),
+                next_default: "/".to_owned(),
+                redirect: true,
+                rejections_only: false,
+                fresh_for: Duration::minutes(15),
+                login_message: None,
+                login_message_level: Level::Info,
+                #[cfg(feature = "axum_layer")]
+                unauthorized_handler: None,
+                #[cfg(feature = "actix_layer")]
+                unauthorized_handler2: None,
+                #[cfg(feature = "axum_layer")]
+                error_renderer: None,
+                #[cfg(feature = "actix_layer")]
+                error_renderer2: None,
+                user_loaders: HashMap::new(),
+                user_cache: None,
+                #[cfg(feature = "axum_layer")]
+                request_loader: None,
+                #[cfg(feature = "actix_layer")]
+                request_loader2: None,
+            }),
         }))
     }
 
-    /// Set false, not redirect when user is not authenticated. Default true.
+    fn config_mut(&mut self) -> &mut Config {
+        Arc::get_mut(&mut Arc::get_mut(&mut self.0).unwrap().config).unwrap()
+    }
+
+    /// Set false, keep the `401` when user is not authenticated. Default true.
+    ///
+    /// Otherwise a `401` is answered by its `Accept`:
+    /// - htmx requests get `200` with `HX-Redirect` to `login_view`.
+    /// - `GET`/`HEAD` accepting `text/html` are redirected to `login_view`.
+    /// - Others get a JSON `{"error": "unauthorized", "login_url": ...}`.
     pub fn redirect(mut self, redirect: bool) -> Self {
-        Arc::get_mut(&mut self.0).unwrap().redirect = redirect;
+        self.config_mut().redirect = redirect;
+        self
+    }
+
+    /// Only answer the `401` rejections of the extractors and `LoginRequired`
+    /// as in `redirect`, keep the `401` returned by the app itself, e.g. a
+    /// webhook with a bad signature. Default every `401` is answered.
+    pub fn rejections_only(mut self) -> Self {
+        self.config_mut().rejections_only = true;
         self
     }
 
     /// Set the login url redirect, default '/login'.
     pub fn login_view<S: Into<String>>(mut self, login_view: S) -> Self {
-        Arc::get_mut(&mut self.0).unwrap().login_view = login_view.into();
+        self.config_mut().login_view = login_view.into();
+        self
+    }
+
+    /// Set the re-authentication url redirect when a `FreshUser` is required,
+    /// default the `login_view`.
+    pub fn refresh_view<S: Into<String>>(mut self, refresh_view: S) -> Self {
+        self.config_mut().refresh_view = Some(refresh_view.into());
+        self
+    }
+
+    /// Set how long a login stays fresh, default 15 minutes.
+    pub fn fresh_for(mut self, fresh_for: Duration) -> Self {
+        self.config_mut().fresh_for = fresh_for;
+        self
+    }
+
+    /// Flash a message when redirecting to `login_view`, see `IncomingFlashes`.
+    /// Default `None`.
+    pub fn login_message<S: Into<String>>(mut self, login_message: S) -> Self {
+        self.config_mut().login_message = Some(login_message.into());
+        self
+    }
+
+    /// Set the level of the `login_message`, default `Level::Info`.
+    pub fn login_message_level(mut self, level: Level) -> Self {
+        self.config_mut().login_message_level = level;
         self
     }
 
     /// Set the query `?next=/url`, default 'next'.
     pub fn next_key<S: Into<String>>(mut self, next_key: S) -> Self {
-        Arc::get_mut(&mut self.0).unwrap().next_key = next_key.into();
+        self.config_mut().next_key = next_key.into();
+        self
+    }
+
+    /// Answer a `401` with a custom response instead, the `login_message` is not flashed.
+    ///
+    /// ## Example
+    /// ```
+    /// use axum::response::{IntoResponse, Redirect};
+    /// use loginmanager::{CookieSession, LoginManager, Unauthorized};
+    ///
+    /// let loginmanager = LoginManager::new(CookieSession::new("secret"))
+    ///     .unauthorized_handler(|unauthorized: &Unauthorized| {
+    ///         Redirect::to(&unauthorized.login_url).into_response()
+    ///     });
+    /// ```
+    #[cfg(feature = "axum_layer")]
+    pub fn unauthorized_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Unauthorized) -> axum::response::Response + Send + Sync + 'static,
+    {
+        self.config_mut().unauthorized_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// `unauthorized_handler` for actix.
+    #[cfg(feature = "actix_layer")]
+    pub fn unauthorized_handler2<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Unauthorized) -> actix_web::HttpResponse + Send + Sync + 'static,
+    {
+        self.config_mut().unauthorized_handler2 = Some(Arc::new(handler));
+        self
+    }
+
+    /// Render the `LoginError` of the extractors, a `401` is still answered
+    /// as described in `redirect` first.
+    ///
+    /// ## Example
+    /// ```
+    /// use axum::{http::StatusCode, response::IntoResponse};
+    /// use loginmanager::{CookieSession, LoginError, LoginManager};
+    ///
+    /// let loginmanager = LoginManager::new(CookieSession::new("secret"))
+    ///     .error_renderer(|err: &LoginError| match err {
+    ///         LoginError::Forbidden => (StatusCode::FORBIDDEN, "Nope.").into_response(),
+    ///         err => (err.status(), err.to_string()).into_response(),
+    ///     });
+    /// ```
+    #[cfg(feature = "axum_layer")]
+    pub fn error_renderer<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(&LoginError) -> axum::response::Response + Send + Sync + 'static,
+    {
+        self.config_mut().error_renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    /// `error_renderer` for actix.
+    #[cfg(feature = "actix_layer")]
+    pub fn error_renderer2<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(&LoginError) -> actix_web::HttpResponse + Send + Sync + 'static,
+    {
+        self.config_mut().error_renderer2 = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Render the `LoginError` as RFC 7807 `application/problem+json`.
+    #[allow(unused_mut)]
+    pub fn problem_json(mut self) -> Self {
+        #[cfg(feature = "axum_layer")]
+        {
+            self = self.error_renderer(LoginError::problem_response);
+        }
+        #[cfg(feature = "actix_layer")]
+        {
+            self = self.error_renderer2(LoginError::problem_response2);
+        }
+        self
+    }
+
+    /// Load the user `U` with a closure instead of `UserMinix::load_user`,
+    /// which can be left out then.
+    ///
+    /// The closure gets a clone of `UserMinix::State`, found like for
+    /// `load_user`: the router state or a `FromRef` of it with axum,
+    /// the `web::Data` with actix.
+    ///
+    /// ## Example
+    /// ```
+    /// use loginmanager::{CookieSession, LoginManager, UserMinix};
+    /// # #[derive(Clone)]
+    /// # struct Db;
+    ///
+    /// #[derive(Clone)]
+    /// struct User {
+    ///     id: i32,
+    /// }
+    ///
+    /// impl UserMinix for User {
+    ///     type Key = i32;
+    ///     type State = Db;
+    ///
+    ///     fn get_id(&self) -> &i32 {
+    ///         &self.id
+    ///     }
+    /// }
+    ///
+    /// let loginmanager = LoginManager::new(CookieSession::new("secret"))
+    ///     .user_loader(|id: i32, db: Db| async move { Ok(Some(User { id })) });
+    /// ```
+    pub fn user_loader<U, F, Fut>(mut self, loader: F) -> Self
+    where
+        U: UserMinix,
+        U::State: Clone,
+        F: Fn(U::Key, U::State) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<U>, LoaderError>> + Send + 'static,
+    {
+        self.config_mut()
+            .user_loaders
+            .insert(TypeId::of::<U>(), Box::new(UserLoader::new(loader)));
+        self
+    }
+
+    /// Keep the loaded users in `cache` between requests, see `UserCache`.
+    pub fn user_cache(mut self, cache: UserCache) -> Self {
+        self.config_mut().user_cache = Some(cache);
+        self
+    }
+
+    /// Authenticate a request without a session, e.g. a signed query or a header.
+    ///
+    /// It runs when the decoder yields no key and an extractor needs the user,
+    /// at most once per request. The user it returns is used by `CurrentUser<U>`
+    /// and `AuthUser<U>` for this request only, nothing is written to the
+    /// session. An `Err` rejects with `LoginError::LoaderFailure`.
+    ///
+    /// ## Example
+    /// ```
+    /// use loginmanager::{CookieSession, LoaderError, LoginManager};
+    /// # #[derive(Clone)]
+    /// # struct User { id: i32 }
+    /// # impl loginmanager::UserMinix for User {
+    /// #     type Key = i32;
+    /// #     type State = ();
+    /// #     fn get_id(&self) -> &i32 { &self.id }
+    /// # }
+    ///
+    /// let loginmanager = LoginManager::new(CookieSession::new("secret"))
+    ///     .request_loader(|parts: &axum::http::request::Parts| {
+    ///         let token = parts.headers.get("x-token").cloned();
+    ///         async move {
+    ///             let user = token.filter(|token| token == "secret").map(|_| User { id: 1 });
+    ///             Ok(user)
+    ///         }
+    ///     });
+    /// ```
+    #[cfg(feature = "axum_layer")]
+    pub fn request_loader<U, F, Fut>(mut self, loader: F) -> Self
+    where
+        U: UserMinix,
+        F: Fn(&http::request::Parts) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<U>, LoaderError>> + Send + 'static,
+    {
+        self.config_mut().request_loader = Some(Arc::new(move |parts| {
+            let fut = loader(parts);
+            Box::pin(async move {
+                Ok(fut.await?.map(|user| {
+                    let mut extensions = http::Extensions::new();
+                    extensions.insert(user);
+                    extensions
+                }))
+            })
+        }));
+        self
+    }
+
+    /// `request_loader` for actix.
+    #[cfg(feature = "actix_layer")]
+    pub fn request_loader2<U, F, Fut>(mut self, loader: F) -> Self
+    where
+        U: UserMinix,
+        F: Fn(&actix_web::HttpRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<U>, LoaderError>> + 'static,
+    {
+        self.config_mut().request_loader2 = Some(Arc::new(move |req| {
+            let fut = loader(req);
+            Box::pin(async move {
+                Ok(fut.await?.map(|user| {
+                    let mut extensions = actix_web::dev::Extensions::new();
+                    extensions.insert(user);
+                    extensions
+                }))
+            })
+        }));
+        self
+    }
+
+    /// Set the redirect after login when `next` is missing or unsafe, see `NextUrl`.
+    /// Default '/'.
+    pub fn next_default<S: Into<String>>(mut self, next_default: S) -> Self {
+        self.config_mut().next_default = next_default.into();
         self
     }
 }
@@ -4,16 +4,46 @@ use std::{
 };
 
 use actix_web::{
+    body::{EitherBody, MessageBody},
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     http::{
-        header::{HeaderValue, LOCATION},
+        header::{ACCEPT, LOCATION, SET_COOKIE, WWW_AUTHENTICATE},
         StatusCode,
     },
-    Error, HttpMessage,
+    Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
 
-use crate::{loginmanager::Inner, DecodeRequest, LoginInfo, LoginManager};
+use crate::{
+    loginmanager::Inner, DecodeRequest, LoginError, LoginInfo, LoginManager, Unauthorized,
+};
+
+fn unauthorized(req: &ServiceRequest) -> Unauthorized {
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    Unauthorized {
+        method: req.method().to_string(),
+        uri: req
+            .uri()
+            .path_and_query()
+            .map_or("/".to_owned(), |p| p.to_string()),
+        accept: header(ACCEPT.as_str()).map(ToOwned::to_owned),
+        htmx: header("hx-request") == Some("true"),
+        login_url: String::new(),
+    }
+}
+
+/// Answer with `answer`, keep the cookies written by the decoder,
+/// e.g. a rotated `RememberMe` token.
+fn with_cookies<B>(
+    mut answer: HttpResponse,
+    res: ServiceResponse<B>,
+) -> ServiceResponse<EitherBody<B>> {
+    for value in res.headers().get_all(SET_COOKIE) {
+        answer.headers_mut().append(SET_COOKIE, value.clone());
+    }
+    let (req, _) = res.into_parts();
+    ServiceResponse::new(req, answer).map_into_right_body()
+}
 
 // Middleware factory is `Transform` trait
 // `S` - type of the next service
@@ -22,10 +52,10 @@ impl<S: 'static, B, D> Transform<S, ServiceRequest> for LoginManager<D>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
     D: DecodeRequest<ServiceRequest, ServiceResponse<B>> + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type InitError = ();
     type Transform = LoginManagerMiddleware<S, D>;
@@ -55,10 +85,10 @@ impl<S, B, D> Service<ServiceRequest> for LoginManagerMiddleware<S, D>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
     D: DecodeRequest<ServiceRequest, ServiceResponse<B>> + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
@@ -69,27 +99,67 @@ where
 
         let loginmanager = self.loginmanger();
         let logininfo = LoginInfo::default();
+        logininfo.set_accepts_challenge(!loginmanager.decoder.is_session());
         req.extensions_mut().insert(logininfo.clone());
+        req.extensions_mut().insert(loginmanager.config.clone());
+        let unauthorized = if loginmanager.config.redirect {
+            Some(unauthorized(&req))
+        } else {
+            None
+        };
 
         Box::pin(async move {
             match loginmanager.decoder.decode2(&mut req).await {
                 Ok(key) => logininfo.set_key(key),
-                Err(res) => return Ok(res),
+                Err(res) => return Ok(res.map_into_left_body()),
             };
+            let config = &loginmanager.config;
             let mut res = serv.call(req).await?;
+
+            let rejected = !config.rejections_only
+                || res
+                    .response()
+                    .error()
+                    .is_some_and(|e| e.as_error::<LoginError>().is_some());
+            let unauthorized =
+                unauthorized.filter(|_| rejected && res.status() == StatusCode::UNAUTHORIZED);
+            if let Some(ref unauthorized) = unauthorized {
+                if config.unauthorized_handler2.is_none()
+                    && (unauthorized.htmx || unauthorized.is_html())
+                {
+                    // before `update2`, the decoder writes the message to the session
+                    config.flash_login_message(&logininfo);
+                }
+            }
             loginmanager.decoder.update2(&mut res).await;
 
-            if loginmanager.redirect && res.status().as_u16() == 401 {
-                res.response_mut().head_mut().status = StatusCode::FOUND;
-                let url = res
-                    .request()
-                    .uri()
-                    .path_and_query()
-                    .map_or("/".to_string(), |p| loginmanager.next_to(p.as_str()));
-                let headervalue = HeaderValue::from_str(&url).unwrap();
-                res.headers_mut().insert(LOCATION, headervalue);
+            // a decoder answered with its own challenge, e.g. `BasicAuth` to a Basic attempt
+            let challenged = res.headers().contains_key(WWW_AUTHENTICATE);
+            if let Some(mut unauthorized) = unauthorized.filter(|_| !challenged) {
+                unauthorized.login_url = config.redirect_to(&unauthorized.uri, &logininfo);
+                let answer = match config.unauthorized_handler2 {
+                    Some(ref handler) => handler(&unauthorized),
+                    // htmx only follows `HX-Redirect` of a successful response
+                    None if unauthorized.htmx => HttpResponse::Ok()
+                        .insert_header(("hx-redirect", unauthorized.login_url.as_str()))
+                        .finish(),
+                    None if unauthorized.is_html() => HttpResponse::Found()
+                        .insert_header((LOCATION, unauthorized.login_url.as_str()))
+                        .finish(),
+                    None => HttpResponse::Unauthorized().json(unauthorized.json()),
+                };
+                return Ok(with_cookies(answer, res));
             };
-            Ok(res)
+            if let Some(ref renderer) = config.error_renderer2 {
+                let err = res
+                    .response()
+                    .error()
+                    .and_then(|e| e.as_error::<LoginError>());
+                if let Some(answer) = err.map(|err| renderer(err)) {
+                    return Ok(with_cookies(answer, res));
+                }
+            }
+            Ok(res.map_into_left_body())
         })
     }
 }
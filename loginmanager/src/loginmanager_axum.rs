@@ -1,7 +1,8 @@
 use axum::{
     body::Body,
-    http::Request,
+    http::{header, Request, StatusCode},
     response::{IntoResponse, Redirect, Response},
+    Json,
 };
 use futures_util::future::BoxFuture;
 use std::{
@@ -12,9 +13,33 @@ use tower_service::Service;
 
 use crate::{
     loginmanager::{DecodeRequest, Inner, LoginInfo},
-    LoginManager,
+    LoginError, LoginManager, Unauthorized,
 };
 
+fn unauthorized(req: &Request<Body>) -> Unauthorized {
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    Unauthorized {
+        method: req.method().to_string(),
+        uri: req
+            .uri()
+            .path_and_query()
+            .map_or("/".to_owned(), |p| p.to_string()),
+        accept: header(header::ACCEPT.as_str()).map(ToOwned::to_owned),
+        htmx: header("hx-request") == Some("true"),
+        login_url: String::new(),
+    }
+}
+
+/// Keep the cookies written by the decoder, e.g. a rotated `RememberMe` token.
+fn with_cookies(mut answer: Response, res: &Response) -> Response {
+    for value in res.headers().get_all(header::SET_COOKIE) {
+        answer
+            .headers_mut()
+            .append(header::SET_COOKIE, value.clone());
+    }
+    answer
+}
+
 impl<S, D> tower_layer::Layer<S> for LoginManager<D> {
     type Service = LoginManagerMiddleware<S, D>;
 
@@ -57,33 +82,65 @@ where
     fn call(&mut self, mut req: Request<Body>) -> Self::Future {
         let _serv = self.serv.clone();
         let mut serv = std::mem::replace(&mut self.serv, _serv);
-        let redirect_url = if self.manager.redirect {
-            req.uri().path_and_query().map(|f| f.to_string())
+        let unauthorized = if self.manager.config.redirect {
+            Some(unauthorized(&req))
         } else {
             None
         };
         let manager = self.loginmanger();
         let logininfo = LoginInfo::default();
+        logininfo.set_accepts_challenge(!manager.decoder.is_session());
         req.extensions_mut().insert(logininfo.clone());
+        req.extensions_mut().insert(manager.config.clone());
 
         Box::pin(async move {
             match manager.decoder.decode(&mut req).await {
                 Ok(key) => logininfo.set_key(key),
                 Err(res) => return Ok(res),
             };
+            let config = &manager.config;
             let mut res = serv.call(req).await?;
             // important for axum
-            res.extensions_mut().insert(logininfo);
+            res.extensions_mut().insert(logininfo.clone());
+
+            let rejected =
+                !config.rejections_only || res.extensions().get::<LoginError>().is_some();
+            let unauthorized =
+                unauthorized.filter(|_| rejected && res.status() == StatusCode::UNAUTHORIZED);
+            if let Some(ref unauthorized) = unauthorized {
+                if config.unauthorized_handler.is_none()
+                    && (unauthorized.htmx || unauthorized.is_html())
+                {
+                    // before `update`, the decoder writes the message to the session
+                    config.flash_login_message(&logininfo);
+                }
+            }
             manager.decoder.update(&mut res).await;
 
-            if manager.redirect && res.status().as_u16() == 401 {
-                let uri = if let Some(uri) = redirect_url {
-                    manager.next_to(&uri)
-                } else {
-                    manager.next_to("/")
+            // a decoder answered with its own challenge, e.g. `BasicAuth` to a Basic attempt
+            let challenged = res.headers().contains_key(header::WWW_AUTHENTICATE);
+            if let Some(mut unauthorized) = unauthorized.filter(|_| !challenged) {
+                unauthorized.login_url = config.redirect_to(&unauthorized.uri, &logininfo);
+                let answer = match config.unauthorized_handler {
+                    Some(ref handler) => handler(&unauthorized),
+                    // htmx only follows `HX-Redirect` of a successful response
+                    None if unauthorized.htmx => (
+                        StatusCode::OK,
+                        [("hx-redirect", unauthorized.login_url.as_str())],
+                    )
+                        .into_response(),
+                    None if unauthorized.is_html() => {
+                        Redirect::to(&unauthorized.login_url).into_response()
+                    }
+                    None => (StatusCode::UNAUTHORIZED, Json(unauthorized.json())).into_response(),
                 };
-                return Ok(Redirect::to(&uri).into_response());
+                return Ok(with_cookies(answer, &res));
             };
+            if let Some(ref renderer) = config.error_renderer {
+                if let Some(err) = res.extensions().get::<LoginError>() {
+                    return Ok(with_cookies(renderer(err), &res));
+                }
+            }
             Ok(res)
         })
     }
@@ -0,0 +1,72 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, FromRequest,
+};
+use futures_util::future::LocalBoxFuture;
+
+use super::LoginRequired;
+use crate::{AuthUser, UserMinix};
+
+impl<S, B, U> Transform<S, ServiceRequest> for LoginRequired<U>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    U: UserMinix,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LoginRequiredMiddleware<S, U>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LoginRequiredMiddleware {
+            service: Rc::new(service),
+            required: self.clone(),
+        }))
+    }
+}
+
+pub struct LoginRequiredMiddleware<S, U> {
+    service: Rc<S>,
+    required: LoginRequired<U>,
+}
+
+impl<S, B, U> Service<ServiceRequest> for LoginRequiredMiddleware<S, U>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+    U: UserMinix,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let serv = self.service.clone();
+        let allowed = self.required.is_allowed(req.path());
+
+        Box::pin(async move {
+            if allowed {
+                return Ok(serv.call(req).await?.map_into_left_body());
+            }
+            let (http_req, mut payload) = req.into_parts();
+            // the user is kept in the extensions by `CurrentUser`
+            if let Err(err) = AuthUser::<U>::from_request(&http_req, &mut payload).await {
+                return Ok(ServiceResponse::from_err(err, http_req).map_into_right_body());
+            }
+            let req = ServiceRequest::from_parts(http_req, payload);
+            Ok(serv.call(req).await?.map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use std::task::{Context, Poll};
+
+use axum::{
+    body::Body,
+    extract::{FromRef, FromRequestParts},
+    http::Request,
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use tower_service::Service;
+
+use super::LoginRequired;
+use crate::{AuthUser, UserMinix};
+
+impl<S, U, St: Clone> tower_layer::Layer<S> for LoginRequired<U, St> {
+    type Service = LoginRequiredService<S, U, St>;
+
+    fn layer(&self, serv: S) -> Self::Service {
+        LoginRequiredService {
+            serv,
+            required: self.clone(),
+        }
+    }
+}
+
+pub struct LoginRequiredService<S, U, St = ()> {
+    serv: S,
+    required: LoginRequired<U, St>,
+}
+
+impl<S: Clone, U, St: Clone> Clone for LoginRequiredService<S, U, St> {
+    fn clone(&self) -> Self {
+        Self {
+            serv: self.serv.clone(),
+            required: self.required.clone(),
+        }
+    }
+}
+
+impl<S, U, St> Service<Request<Body>> for LoginRequiredService<S, U, St>
+where
+    S: Service<Request<Body>, Response = Response> + Send + Clone + 'static,
+    S::Future: Send + 'static,
+    U: UserMinix,
+    U::State: FromRef<St>,
+    St: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.serv.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let _serv = self.serv.clone();
+        let mut serv = std::mem::replace(&mut self.serv, _serv);
+        if self.required.is_allowed(req.uri().path()) {
+            return Box::pin(serv.call(req));
+        }
+
+        let state = self.required.state.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            // the user is kept in the extensions by `CurrentUser`
+            if let Err(rejection) = AuthUser::<U>::from_request_parts(&mut parts, &state).await {
+                return Ok(rejection.into_response());
+            }
+            serv.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
@@ -1,4 +1,122 @@
+#[cfg(feature = "actix_layer")]
+mod actix;
 #[cfg(feature = "axum_layer")]
 mod axum;
 
-pub struct LoginRequired<U>(Option<U>);
+use std::{marker::PhantomData, sync::Arc};
+
+/// LoginRequired<U> protects all routes it wraps, use it inside the `LoginManager`.
+///
+/// The user `U` is loaded once and kept in the request extensions,
+/// so the `AuthUser<U>`/`CurrentUser<U>` extractors downstream reuse it.
+/// Requests without an authenticated and active user are rejected with 401,
+/// which the `LoginManager` redirects to `login_view`.
+///
+/// With axum a layer does not see the router state, give the state the user
+/// is loaded with to [`LoginRequired::with_state`].
+///
+/// ## Example
+/// ```no_run
+/// # use axum::{routing::get, Router};
+/// # use loginmanager::{AuthUser, LoginRequired, UserMinix};
+/// # #[derive(Clone)]
+/// # struct User { id: i32 }
+/// # #[async_trait::async_trait]
+/// # impl UserMinix for User {
+/// #     type Key = i32;
+/// #     type State = ();
+/// #     fn get_id(&self) -> &i32 { &self.id }
+/// # }
+/// # async fn hello_user(AuthUser(user): AuthUser<User>) -> String { user.id.to_string() }
+/// # async fn public() -> &'static str { "public" }
+/// let api: Router = Router::new()
+///     .route("/:path", get(hello_user))
+///     .route("/public", get(public))
+///     .route_layer(LoginRequired::<User>::new().allow("/public"));
+/// ```
+pub struct LoginRequired<U, St = ()> {
+    allow: Arc<Vec<String>>,
+    state: St,
+    _user: PhantomData<fn() -> U>,
+}
+
+impl<U, St: Clone> Clone for LoginRequired<U, St> {
+    fn clone(&self) -> Self {
+        Self {
+            allow: self.allow.clone(),
+            state: self.state.clone(),
+            _user: PhantomData,
+        }
+    }
+}
+
+impl<U> Default for LoginRequired<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U> LoginRequired<U> {
+    pub fn new() -> Self {
+        Self {
+            allow: Arc::new(Vec::new()),
+            state: (),
+            _user: PhantomData,
+        }
+    }
+}
+
+impl<U, St> LoginRequired<U, St> {
+    /// Load the user with the router state of axum, e.g. for a
+    /// `LoginManager::user_loader` of the router state or a `FromRef` of it.
+    ///
+    /// ```no_run
+    /// # use axum::{routing::get, Router};
+    /// # use loginmanager::{AuthUser, LoaderError, LoginRequired, UserMinix};
+    /// # #[derive(Clone)]
+    /// # struct User { id: i32 }
+    /// # #[derive(Clone)]
+    /// # struct AppState;
+    /// # #[async_trait::async_trait]
+    /// # impl UserMinix for User {
+    /// #     type Key = i32;
+    /// #     type State = AppState;
+    /// #     async fn load_user(id: &i32, _: &AppState) -> Result<Option<Self>, LoaderError> {
+    /// #         Ok(Some(User { id: *id }))
+    /// #     }
+    /// #     fn get_id(&self) -> &i32 { &self.id }
+    /// # }
+    /// # async fn hello_user(AuthUser(user): AuthUser<User>) -> String { user.id.to_string() }
+    /// # let state = AppState;
+    /// let api: Router = Router::new()
+    ///     .route("/:path", get(hello_user))
+    ///     .route_layer(LoginRequired::<User>::new().with_state(state.clone()))
+    ///     .with_state(state);
+    /// ```
+    pub fn with_state<S>(self, state: S) -> LoginRequired<U, S> {
+        LoginRequired {
+            allow: self.allow,
+            state,
+            _user: PhantomData,
+        }
+    }
+
+    /// Let the path pass without login, a trailing `*` matches any suffix,
+    /// e.g. `/static/*`.
+    ///
+    /// **Tip**: with axum the path is relative to the nested router,
+    /// with actix it is the full request path.
+    pub fn allow<S: Into<String>>(mut self, path: S) -> Self {
+        Arc::make_mut(&mut self.allow).push(path.into());
+        self
+    }
+
+    pub(crate) fn is_allowed(&self, path: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|allow| match allow.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == allow,
+            })
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(feature = "actix_layer")]
+use actix_web::{HttpMessage, HttpResponse, Responder};
+#[cfg(feature = "axum_layer")]
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Redirect, Response},
+};
+#[cfg(feature = "actix_layer")]
+use futures_util::future::LocalBoxFuture;
+#[cfg(any(feature = "axum_layer", feature = "actix_layer"))]
+use std::sync::Arc;
+
+use crate::{loginmanager::Config, LoginError};
+
+/// Only a path of this site, not `https://evil.com`, `//evil.com` or `/\evil.com`.
+fn is_safe(next: &str) -> bool {
+    next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.contains('\\')
+        && !next.chars().any(char::is_control)
+}
+
+/// Read `next_key` from the query, the default of the config if it is missing or unsafe.
+fn next_from_query(config: &Config, query: Option<&str>) -> String {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            if k == config.next_key {
+                urlencoding::decode(&v.replace('+', " "))
+                    .ok()
+                    .map(|v| v.into_owned())
+            } else {
+                None
+            }
+        })
+        .filter(|next| is_safe(next))
+        .unwrap_or_else(|| config.next_default.clone())
+}
+
+/// `NextUrl` Extractor
+///
+/// The `?next=/url` added when redirecting to `login_view`, checked to be
+/// a path of this site, otherwise the `next_default` of the `LoginManager`.
+/// Return it from the login handler to redirect back with `303 See Other`.
+///
+/// ## Example
+/// ```
+/// use loginmanager::{AuthContext, NextUrl};
+///
+/// async fn login_post(mut auth_context: AuthContext, next: NextUrl) -> NextUrl {
+///     // check the password, then `auth_context.login(&user)`
+///     next
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextUrl(pub String);
+
+impl NextUrl {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<S> FromRequestParts<S> for NextUrl
+where
+    S: Send + Sync,
+{
+    type Rejection = LoginError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Arc<Config>>()
+            .map(|config| Self(next_from_query(config, parts.uri.query())))
+            .ok_or(LoginError::MissingMiddleware)
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+impl IntoResponse for NextUrl {
+    fn into_response(self) -> Response {
+        Redirect::to(&self.0).into_response()
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+impl actix_web::FromRequest for NextUrl {
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            req.extensions()
+                .get::<Arc<Config>>()
+                .map(|config| Self(next_from_query(config, Some(req.query_string()))))
+                .ok_or(LoginError::MissingMiddleware.into())
+        })
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+impl Responder for NextUrl {
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::SeeOther()
+            .insert_header((actix_web::http::header::LOCATION, self.0))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoginManager;
+
+    #[test]
+    fn unsafe_next() {
+        for next in [
+            "//evil.com",
+            "/\\evil.com",
+            "https://evil.com",
+            "javascript:alert(1)",
+            "/\tevil",
+            "/foo\r\nLocation: https://evil.com",
+            "",
+        ] {
+            assert!(!is_safe(next), "{next:?}");
+        }
+        assert!(is_safe("/"));
+        assert!(is_safe("/user/1?tab=a%2Fb"));
+    }
+
+    #[test]
+    fn next_or_default() {
+        let manager = LoginManager::new(()).next_default("/home");
+        let config = &manager.0.config;
+        let next = |query| next_from_query(config, query);
+        assert_eq!(next(Some("next=%2Fuser%2F1%3Ftab%3Dx")), "/user/1?tab=x");
+        assert_eq!(next(Some("a=1&next=/user")), "/user");
+        for query in [
+            "next=%2F%2Fevil.com",
+            "next=//evil.com",
+            "next=%2F%5Cevil.com",
+            "next=https%3A%2F%2Fevil.com",
+            "next=javascript:alert(1)",
+            "next=%2F%0D%0Aevil",
+            "next=%FF",
+            "other=/user",
+        ] {
+            assert_eq!(next(Some(query)), "/home", "{query}");
+        }
+        assert_eq!(next(None), "/home");
+    }
+}
@@ -0,0 +1,389 @@
+use std::sync::Arc;
+
+#[cfg(feature = "actix_layer")]
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    HttpMessage,
+};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{
+    body::Body,
+    response::{IntoResponse, Response},
+};
+use cookie::{Cookie, SameSite};
+#[cfg(feature = "axum_layer")]
+use http::{header, HeaderValue, Request, StatusCode};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use time::{Duration, OffsetDateTime};
+
+use crate::cooke_session::find_cookie;
+use crate::loginmanager::{DecodeRequest, LoginInfo};
+use crate::store::{random_id, SessionRecord, SessionStore, StoreError};
+
+/// Wrap a decoder to keep users logged in with a long-lived remember-me cookie,
+/// issued by `AuthContext::login_with(&user, LoginOptions { remember: true })`.
+///
+/// The cookie holds a random series and token, only the hash of the token is
+/// kept in the [`SessionStore`]. When the inner decoder finds no user, the
+/// series restores the login and the token is rotated. The previous token
+/// stays valid for a short [`RememberMe::grace`], e.g. for the concurrent
+/// requests of a page sent with the same cookie. After it, a wrong token for a
+/// known series means the cookie was stolen and the series is revoked.
+///
+/// ```
+/// use loginmanager::{CookieSession, LoginManager, MemoryStore, RememberMe};
+///
+/// let loginmanager = LoginManager::new(RememberMe::new(
+///     CookieSession::new("secret"),
+///     MemoryStore::new(),
+/// ));
+/// ```
+pub struct RememberMe<D, S> {
+    inner: D,
+    store: Arc<S>,
+    name: String,
+    path: String,
+    domain: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    duration: Duration,
+    grace: Duration,
+}
+
+impl<D: Clone, S> Clone for RememberMe<D, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            store: self.store.clone(),
+            name: self.name.clone(),
+            path: self.path.clone(),
+            domain: self.domain.clone(),
+            secure: self.secure,
+            http_only: self.http_only,
+            same_site: self.same_site,
+            duration: self.duration,
+            grace: self.grace,
+        }
+    }
+}
+
+impl<D, S: SessionStore> RememberMe<D, S> {
+    pub fn new(inner: D, store: S) -> Self {
+        Self {
+            inner,
+            store: Arc::new(store),
+            name: "_remember".to_owned(),
+            path: "/".to_owned(),
+            domain: None,
+            secure: true,
+            http_only: true,
+            same_site: None,
+            duration: Duration::days(30),
+            grace: Duration::seconds(30),
+        }
+    }
+
+    /// Set cookie name, Default: `_remember`
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn path(mut self, path: String) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Cookie requires Secure or not, Default `true`
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Cookie uses HttpOnly or not, Default `true`
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn domain(mut self, domain: Option<String>) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Cookie requires same origin, Default `None`
+    pub fn same_site(mut self, same_site: Option<SameSite>) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// How long the user is remembered, Default: 30 days
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// How long the previous token is accepted after a rotation, Default: 30 seconds
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    fn build_cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.name.clone(), value);
+        cookie.set_path(self.path.clone());
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        if let Some(ref domain) = self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        if let Some(same_site) = self.same_site {
+            cookie.set_same_site(same_site);
+        }
+        if cookie.value().is_empty() {
+            cookie.make_removal();
+        } else {
+            cookie.set_max_age(self.duration);
+        }
+        cookie
+    }
+
+    fn record_id(series: &str) -> String {
+        format!("remember:{}", series)
+    }
+
+    fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Save a new token for the series, return the cookie value.
+    ///
+    /// `previous` is the hash of the rotated token, `epoch` the one of the user.
+    async fn issue(
+        &self,
+        series: &str,
+        user_key: String,
+        previous: Option<String>,
+        epoch: Option<String>,
+    ) -> Result<String, StoreError> {
+        let token = random_id();
+        let record = SessionRecord {
+            user_key: Some(user_key),
+            epoch,
+            token: Some(Self::hash(&token)),
+            rotated_at: previous
+                .is_some()
+                .then(|| OffsetDateTime::now_utc().unix_timestamp()),
+            previous_token: previous,
+            ..Default::default()
+        };
+        self.store
+            .save(&Self::record_id(series), &record, self.duration)
+            .await?;
+        Ok(format!("{}:{}", series, token))
+    }
+
+    /// Restore the login from the remember-me cookie and rotate its token.
+    async fn restore(
+        &self,
+        login_info: &LoginInfo,
+        token: Option<String>,
+    ) -> Result<Option<String>, StoreError> {
+        let (series, token) = match (login_info.remember_series(), token) {
+            (Some(series), Some(token)) => (series, token),
+            _ => return Ok(None),
+        };
+        // the cookie is cleared unless the token is valid
+        login_info.set_remember_cookie(Some(String::new()));
+
+        let id = Self::record_id(&series);
+        let record = match self.store.load(&id).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let hash = Self::hash(&token);
+        let matches = |stored: &Option<String>| {
+            stored
+                .as_deref()
+                .is_some_and(|stored| bool::from(stored.as_bytes().ct_eq(hash.as_bytes())))
+        };
+        if !matches(&record.token) {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let in_grace = record
+                .rotated_at
+                .is_some_and(|rotated_at| now - rotated_at <= self.grace.whole_seconds());
+            if in_grace && matches(&record.previous_token) {
+                // a concurrent request rotated the token, its response sets the cookie
+                login_info.set_remember_cookie(None);
+                login_info.set_epoch(Some(record.epoch));
+                return Ok(record.user_key);
+            }
+            // the token was rotated before: the cookie was stolen
+            self.store.delete(&id).await?;
+            return Ok(None);
+        }
+        let key = match record.user_key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let value = self
+            .issue(&series, key.clone(), record.token, record.epoch.clone())
+            .await?;
+        login_info.set_remember_cookie(Some(value));
+        // let the inner decoder write a new session, the credentials were not entered
+        login_info.login(key.clone());
+        login_info.set_fresh(false);
+        // a changed epoch logs out, and the logout revokes the series
+        login_info.set_epoch(Some(record.epoch));
+        Ok(Some(key))
+    }
+
+    /// Read the series of the remember-me cookie into the login info, return the token.
+    fn read_cookie<'a, I>(&self, values: I, login_info: &LoginInfo) -> Option<String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let cookie = find_cookie(values, &self.name)?;
+        let (series, token) = cookie.value().split_once(':')?;
+        login_info.set_remember_series(Some(series.to_owned()));
+        Some(token.to_owned())
+    }
+
+    /// Persist the remember-me state, return the cookie to send.
+    async fn save_remember(
+        &self,
+        login_info: &LoginInfo,
+    ) -> Result<Option<Cookie<'static>>, StoreError> {
+        if login_info.is_data_only() {
+            return Ok(None);
+        }
+        let series = login_info.remember_series();
+        if login_info.is_logout() {
+            return match series {
+                Some(series) => {
+                    self.store.delete(&Self::record_id(&series)).await?;
+                    Ok(Some(self.build_cookie(String::new())))
+                }
+                None => Ok(None),
+            };
+        }
+        if let Some(value) = login_info.remember_cookie() {
+            return Ok(Some(self.build_cookie(value)));
+        }
+        if let Some(key) = login_info.login_key() {
+            if let Some(ref series) = series {
+                self.store.delete(&Self::record_id(series)).await?;
+            }
+            if login_info.is_remember() {
+                let epoch = login_info.epoch().flatten();
+                let value = self.issue(&random_id(), key, None, epoch).await?;
+                return Ok(Some(self.build_cookie(value)));
+            }
+            if series.is_some() {
+                return Ok(Some(self.build_cookie(String::new())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<D, S> DecodeRequest<Request<Body>, Response> for RememberMe<D, S>
+where
+    D: DecodeRequest<Request<Body>, Response> + Sync,
+    S: SessionStore,
+{
+    fn is_session(&self) -> bool {
+        true
+    }
+
+    async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+        let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+        let values = req
+            .headers()
+            .get_all(header::COOKIE)
+            .into_iter()
+            .filter_map(|hdr| hdr.to_str().ok());
+        let token = self.read_cookie(values, &login_info);
+        if let Some(key) = self.inner.decode(req).await? {
+            return Ok(Some(key));
+        }
+        self.restore(&login_info, token)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    }
+
+    async fn update(&self, res: &mut Response) {
+        let login_info = res.extensions().get::<LoginInfo>().unwrap().clone();
+        match self.save_remember(&login_info).await {
+            Ok(Some(cookie)) => {
+                res.headers_mut().append(
+                    header::SET_COOKIE,
+                    HeaderValue::from_str(&cookie.encoded().to_string()).unwrap(),
+                );
+            }
+            Ok(None) => {}
+            Err(_) => *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR,
+        }
+        self.inner.update(res).await;
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+#[async_trait(?Send)]
+impl<D, S> DecodeRequest<ServiceRequest, ServiceResponse> for RememberMe<D, S>
+where
+    D: DecodeRequest<ServiceRequest, ServiceResponse>,
+    S: SessionStore,
+{
+    fn is_session(&self) -> bool {
+        true
+    }
+
+    async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
+        let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+        let values = req
+            .headers()
+            .get_all(actix_web::http::header::COOKIE)
+            .filter_map(|hdr| hdr.to_str().ok());
+        let token = self.read_cookie(values, &login_info);
+        if let Some(key) = self.inner.decode2(req).await? {
+            return Ok(Some(key));
+        }
+        match self.restore(&login_info, token).await {
+            Ok(key) => Ok(key),
+            Err(_) => Err(ServiceResponse::new(
+                req.request().clone(),
+                actix_web::HttpResponse::InternalServerError().finish(),
+            )),
+        }
+    }
+
+    async fn update2(&self, res: &mut ServiceResponse) {
+        let login_info = res
+            .request()
+            .extensions()
+            .get::<LoginInfo>()
+            .unwrap()
+            .clone();
+        match self.save_remember(&login_info).await {
+            Ok(Some(cookie)) => {
+                let val =
+                    actix_web::http::header::HeaderValue::from_str(&cookie.encoded().to_string())
+                        .unwrap();
+                res.headers_mut()
+                    .append(actix_web::http::header::SET_COOKIE, val);
+            }
+            Ok(None) => {}
+            Err(_) => {
+                res.response_mut().head_mut().status =
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+        self.inner.update2(res).await;
+    }
+}
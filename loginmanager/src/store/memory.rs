@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use time::{Duration, OffsetDateTime};
+
+use super::{SessionRecord, SessionStore, StoreError};
+
+/// How often `save` removes the expired sessions.
+const SWEEP_INTERVAL: Duration = Duration::minutes(1);
+
+struct Entry {
+    record: SessionRecord,
+    expires_at: OffsetDateTime,
+}
+
+impl Entry {
+    fn is_alive(&self, now: OffsetDateTime) -> bool {
+        self.expires_at > now
+    }
+}
+
+struct Sessions {
+    entries: HashMap<String, Entry>,
+    swept_at: OffsetDateTime,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            swept_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// In-memory [`SessionStore`], sessions are lost when the process exits.
+///
+/// Clones share the same sessions.
+#[derive(Clone, Default)]
+pub struct MemoryStore(Arc<RwLock<Sessions>>);
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all expired sessions.
+    pub fn cleanup(&self) {
+        let now = OffsetDateTime::now_utc();
+        let mut sessions = self.0.write().unwrap();
+        sessions.entries.retain(|_, entry| entry.is_alive(now));
+        sessions.swept_at = now;
+    }
+
+    /// All alive sessions as `(session id, record)`.
+    pub fn sessions(&self) -> Vec<(String, SessionRecord)> {
+        let now = OffsetDateTime::now_utc();
+        self.0
+            .read()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.is_alive(now))
+            .map(|(id, entry)| (id.clone(), entry.record.clone()))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, StoreError> {
+        let now = OffsetDateTime::now_utc();
+        Ok(self
+            .0
+            .read()
+            .unwrap()
+            .entries
+            .get(id)
+            .filter(|entry| entry.is_alive(now))
+            .map(|entry| SessionRecord {
+                expires_at: Some(entry.expires_at.unix_timestamp()),
+                ..entry.record.clone()
+            }))
+    }
+
+    async fn save(
+        &self,
+        id: &str,
+        record: &SessionRecord,
+        ttl: Duration,
+    ) -> Result<(), StoreError> {
+        let now = OffsetDateTime::now_utc();
+        let mut sessions = self.0.write().unwrap();
+        if now - sessions.swept_at >= SWEEP_INTERVAL {
+            sessions.entries.retain(|_, entry| entry.is_alive(now));
+            sessions.swept_at = now;
+        }
+        sessions.entries.insert(
+            id.to_owned(),
+            Entry {
+                record: record.clone(),
+                expires_at: now + ttl,
+            },
+        );
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), StoreError> {
+        self.0.write().unwrap().entries.remove(id);
+        Ok(())
+    }
+
+    async fn touch(&self, id: &str, ttl: Duration) -> Result<(), StoreError> {
+        if let Some(entry) = self.0.write().unwrap().entries.get_mut(id) {
+            entry.expires_at = OffsetDateTime::now_utc() + ttl;
+        }
+        Ok(())
+    }
+
+    async fn delete_user(&self, user_key: &str) -> Result<(), StoreError> {
+        self.0
+            .write()
+            .unwrap()
+            .entries
+            .retain(|_, entry| entry.record.user_key.as_deref() != Some(user_key));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(user_key: &str) -> SessionRecord {
+        SessionRecord {
+            user_key: Some(user_key.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn save_load_delete() {
+        let store = MemoryStore::new();
+        assert_eq!(store.load("a").await.unwrap(), None);
+        store
+            .save("a", &record("1"), Duration::hours(1))
+            .await
+            .unwrap();
+        let loaded = store.load("a").await.unwrap().unwrap();
+        assert_eq!(loaded.user_key.as_deref(), Some("1"));
+        assert!(loaded.expires_at.is_some());
+        store.delete("a").await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn expired_is_missing() {
+        let store = MemoryStore::new();
+        store.save("a", &record("1"), Duration::ZERO).await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), None);
+        assert!(store.sessions().is_empty());
+        store.cleanup();
+        assert!(store.0.read().unwrap().entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn touch_extends() {
+        let store = MemoryStore::new();
+        store.save("a", &record("1"), Duration::ZERO).await.unwrap();
+        store.touch("a", Duration::hours(1)).await.unwrap();
+        let loaded = store.load("a").await.unwrap().unwrap();
+        let left = loaded.expires_at.unwrap() - OffsetDateTime::now_utc().unix_timestamp();
+        assert!(left > 3500);
+    }
+
+    #[tokio::test]
+    async fn save_sweeps_periodically() {
+        let store = MemoryStore::new();
+        store.save("a", &record("1"), Duration::ZERO).await.unwrap();
+        store
+            .save("b", &record("1"), Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(store.0.read().unwrap().entries.len(), 2);
+        store.0.write().unwrap().swept_at -= SWEEP_INTERVAL;
+        store
+            .save("c", &record("1"), Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(store.0.read().unwrap().entries.len(), 2);
+        assert_eq!(store.load("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn delete_user() {
+        let store = MemoryStore::new();
+        store
+            .save("a", &record("1"), Duration::hours(1))
+            .await
+            .unwrap();
+        store
+            .save("b", &record("1"), Duration::hours(1))
+            .await
+            .unwrap();
+        store
+            .save("c", &record("2"), Duration::hours(1))
+            .await
+            .unwrap();
+        store.delete_user("1").await.unwrap();
+        let sessions = store.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].0, "c");
+    }
+}
@@ -0,0 +1,445 @@
+mod memory;
+#[cfg(feature = "redis_store")]
+mod redis;
+#[cfg(feature = "sqlite_store")]
+mod sqlite;
+
+use std::sync::Arc;
+
+#[cfg(feature = "actix_layer")]
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    HttpMessage,
+};
+use async_trait::async_trait;
+#[cfg(feature = "axum_layer")]
+use axum::{
+    body::Body,
+    response::{IntoResponse, Response},
+};
+use cookie::{Cookie, SameSite};
+#[cfg(feature = "axum_layer")]
+use http::{header, HeaderValue, Request, StatusCode};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use time::{Duration, OffsetDateTime};
+
+use crate::cooke_session::find_cookie;
+use crate::loginmanager::{DecodeRequest, LoginInfo};
+
+#[cfg(feature = "redis_store")]
+pub use self::redis::RedisStore;
+pub use memory::MemoryStore;
+#[cfg(feature = "sqlite_store")]
+pub use sqlite::SqliteStore;
+
+/// The error returned by a [`SessionStore`] backend.
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The data kept on the server for one session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// The serialized user key, `None` for an anonymous session.
+    pub user_key: Option<String>,
+    /// The hashed token of a remember-me series.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// The hashed token the series had before its last rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_token: Option<String>,
+    /// Unix time the token of the series was rotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<i64>,
+    /// Unix time the credentials were entered, see [`LoginInfo::login_at`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login_at: Option<i64>,
+    /// The login was made with the credentials, see [`LoginInfo::is_fresh`].
+    #[serde(default)]
+    pub fresh: bool,
+    /// See [`crate::UserMinix::session_epoch`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<String>,
+    /// The custom data, see [`crate::SessionData`].
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub data: Map<String, Value>,
+    /// Unix time the session expires, set by [`SessionStore::load`].
+    ///
+    /// [`StoreSession`] touches the session only when less than half of its
+    /// ttl is left, or on every request when it is `None`.
+    #[serde(skip)]
+    pub expires_at: Option<i64>,
+}
+
+/// Backend of [`StoreSession`], sessions are addressed by a random session id.
+///
+/// Implementations must treat expired sessions as missing.
+#[async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    /// Load the session, `None` if it does not exist or is expired.
+    ///
+    /// Set [`SessionRecord::expires_at`] to spare the `touch` of most requests.
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, StoreError>;
+
+    /// Create or replace the session, it expires after `ttl`.
+    async fn save(&self, id: &str, record: &SessionRecord, ttl: Duration)
+        -> Result<(), StoreError>;
+
+    /// Remove the session.
+    async fn delete(&self, id: &str) -> Result<(), StoreError>;
+
+    /// Reset the expiry of the session to `ttl` from now.
+    async fn touch(&self, id: &str, ttl: Duration) -> Result<(), StoreError>;
+
+    /// Remove all sessions of the user, e.g. log the user out everywhere.
+    async fn delete_user(&self, user_key: &str) -> Result<(), StoreError> {
+        let _ = user_key;
+        Err("delete_user is not supported by this store".into())
+    }
+}
+
+/// Generate a random session identifier.
+pub(crate) fn random_id() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Server-side session, the cookie only carries an opaque session id
+/// and the user key is kept in a [`SessionStore`].
+///
+/// Sessions are revoked by removing them from the store.
+pub struct StoreSession<S> {
+    store: Arc<S>,
+    name: String,
+    path: String,
+    domain: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    ttl: Duration,
+}
+
+impl<S> Clone for StoreSession<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            name: self.name.clone(),
+            path: self.path.clone(),
+            domain: self.domain.clone(),
+            secure: self.secure,
+            http_only: self.http_only,
+            same_site: self.same_site,
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl<S: SessionStore> StoreSession<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            name: "_session_id".to_owned(),
+            path: "/".to_owned(),
+            domain: None,
+            secure: true,
+            http_only: true,
+            same_site: None,
+            ttl: Duration::days(1),
+        }
+    }
+
+    /// The underlying store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Set cookie name, Default: `_session_id`
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn path(mut self, path: String) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Cookie requires Secure or not, Default `true`
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Cookie uses HttpOnly or not, Default `true`
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn domain(mut self, domain: Option<String>) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Cookie requires same origin, Default `None`
+    pub fn same_site(mut self, same_site: Option<SameSite>) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Session expires when it is not used for `ttl`, Default: 1 day
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn build_cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.name.clone(), value);
+        cookie.set_path(self.path.clone());
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        if let Some(ref domain) = self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        if let Some(same_site) = self.same_site {
+            cookie.set_same_site(same_site);
+        }
+        cookie
+    }
+
+    /// Get the user key of session `id` and extend its lifetime.
+    async fn load_key(
+        &self,
+        id: String,
+        login_info: &LoginInfo,
+    ) -> Result<Option<String>, StoreError> {
+        let record = match self.store.load(&id).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        // a `RememberMe` series, not a session
+        if record.token.is_some() {
+            return Ok(None);
+        }
+        // like the sliding renewal of `CookieSession`, not a write per request
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let half = self.ttl.whole_seconds() / 2;
+        if !matches!(record.expires_at, Some(expires_at) if expires_at - now >= half) {
+            self.store.touch(&id, self.ttl).await?;
+        }
+        login_info.set_session_id(Some(id));
+        login_info.set_data(record.data);
+        if record.user_key.is_some() {
+            login_info.set_login_at(record.login_at);
+            login_info.set_fresh(record.fresh);
+            login_info.set_epoch(Some(record.epoch));
+        }
+        Ok(record.user_key)
+    }
+
+    fn record(&self, user_key: Option<String>, login_info: &LoginInfo) -> SessionRecord {
+        SessionRecord {
+            user_key,
+            login_at: login_info.login_at_timestamp(),
+            fresh: login_info.is_fresh(),
+            epoch: login_info.epoch().flatten(),
+            data: login_info.data(),
+            ..Default::default()
+        }
+    }
+
+    /// Persist the login, logout or data of the request, return the cookie to send.
+    ///
+    /// A new session id is issued on every login or regenerate, and the
+    /// record of the previous id is removed.
+    async fn save_login(
+        &self,
+        login_info: &LoginInfo,
+    ) -> Result<Option<Cookie<'static>>, StoreError> {
+        // the user of another decoder is not written to the session
+        let (pending, user_key) = match login_info.is_data_only() {
+            true => (None, None),
+            false => (login_info.pending_key(), login_info.get_key()),
+        };
+        let key = match pending {
+            Some(key) => key,
+            None if login_info.is_data_changed() => match login_info.session_id() {
+                Some(id) => {
+                    // only the data changed, keep the session id
+                    let record = self.record(user_key, login_info);
+                    self.store.save(&id, &record, self.ttl).await?;
+                    return Ok(None);
+                }
+                None => None,
+            },
+            None => return Ok(None),
+        };
+        if let Some(id) = login_info.session_id() {
+            self.store.delete(&id).await?;
+        }
+        if key.is_none() && login_info.with_data(Map::is_empty) {
+            let mut cookie = self.build_cookie("".to_owned());
+            cookie.make_removal();
+            return Ok(Some(cookie));
+        }
+        let id = random_id();
+        let record = self.record(key, login_info);
+        self.store.save(&id, &record, self.ttl).await?;
+        login_info.set_session_id(Some(id.clone()));
+        Ok(Some(self.build_cookie(id)))
+    }
+}
+
+#[cfg(feature = "axum_layer")]
+#[async_trait]
+impl<S: SessionStore> DecodeRequest<Request<Body>, Response> for StoreSession<S> {
+    fn is_session(&self) -> bool {
+        true
+    }
+
+    async fn decode(&self, req: &mut Request<Body>) -> Result<Option<String>, Response> {
+        let values = req
+            .headers()
+            .get_all(header::COOKIE)
+            .into_iter()
+            .filter_map(|hdr| hdr.to_str().ok());
+        let id = match find_cookie(values, &self.name) {
+            Some(cookie) => cookie.value().to_owned(),
+            None => return Ok(None),
+        };
+        let login_info = req.extensions().get::<LoginInfo>().unwrap();
+        match self.load_key(id, login_info).await {
+            Ok(key) => Ok(key),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        }
+    }
+
+    async fn update(&self, res: &mut Response) {
+        let login_info = res.extensions().get::<LoginInfo>().unwrap().clone();
+        match self.save_login(&login_info).await {
+            Ok(Some(cookie)) => {
+                res.headers_mut().append(
+                    header::SET_COOKIE,
+                    HeaderValue::from_str(&cookie.encoded().to_string()).unwrap(),
+                );
+            }
+            Ok(None) => {}
+            Err(_) => *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[cfg(feature = "actix_layer")]
+#[async_trait(?Send)]
+impl<S: SessionStore> DecodeRequest<ServiceRequest, ServiceResponse> for StoreSession<S> {
+    fn is_session(&self) -> bool {
+        true
+    }
+
+    async fn decode2(&self, req: &mut ServiceRequest) -> Result<Option<String>, ServiceResponse> {
+        let values = req
+            .headers()
+            .get_all(actix_web::http::header::COOKIE)
+            .filter_map(|hdr| hdr.to_str().ok());
+        let id = match find_cookie(values, &self.name) {
+            Some(cookie) => cookie.value().to_owned(),
+            None => return Ok(None),
+        };
+        let login_info = req.extensions().get::<LoginInfo>().unwrap().clone();
+        match self.load_key(id, &login_info).await {
+            Ok(key) => Ok(key),
+            Err(_) => Err(ServiceResponse::new(
+                req.request().clone(),
+                actix_web::HttpResponse::InternalServerError().finish(),
+            )),
+        }
+    }
+
+    async fn update2(&self, res: &mut ServiceResponse) {
+        let login_info = res
+            .request()
+            .extensions()
+            .get::<LoginInfo>()
+            .unwrap()
+            .clone();
+        match self.save_login(&login_info).await {
+            Ok(Some(cookie)) => {
+                let val =
+                    actix_web::http::header::HeaderValue::from_str(&cookie.encoded().to_string())
+                        .unwrap();
+                res.headers_mut()
+                    .append(actix_web::http::header::SET_COOKIE, val);
+            }
+            Ok(None) => {}
+            Err(_) => {
+                res.response_mut().head_mut().status =
+                    actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Count the touches of a `MemoryStore`.
+    #[derive(Default)]
+    struct Touches(MemoryStore, AtomicUsize);
+
+    #[async_trait]
+    impl SessionStore for Touches {
+        async fn load(&self, id: &str) -> Result<Option<SessionRecord>, StoreError> {
+            self.0.load(id).await
+        }
+
+        async fn save(
+            &self,
+            id: &str,
+            record: &SessionRecord,
+            ttl: Duration,
+        ) -> Result<(), StoreError> {
+            self.0.save(id, record, ttl).await
+        }
+
+        async fn delete(&self, id: &str) -> Result<(), StoreError> {
+            self.0.delete(id).await
+        }
+
+        async fn touch(&self, id: &str, ttl: Duration) -> Result<(), StoreError> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.touch(id, ttl).await
+        }
+    }
+
+    #[tokio::test]
+    async fn touch_after_half_ttl() {
+        let session = StoreSession::new(Touches::default()).ttl(Duration::hours(1));
+        let record = SessionRecord {
+            user_key: Some("1".to_owned()),
+            ..Default::default()
+        };
+        let store = session.store();
+        store.save("a", &record, Duration::hours(1)).await.unwrap();
+        let key = session
+            .load_key("a".to_owned(), &LoginInfo::default())
+            .await;
+        assert_eq!(key.unwrap().as_deref(), Some("1"));
+        assert_eq!(store.1.load(Ordering::SeqCst), 0);
+
+        store
+            .save("a", &record, Duration::minutes(20))
+            .await
+            .unwrap();
+        session
+            .load_key("a".to_owned(), &LoginInfo::default())
+            .await
+            .unwrap();
+        assert_eq!(store.1.load(Ordering::SeqCst), 1);
+        let expires_at = store.load("a").await.unwrap().unwrap().expires_at.unwrap();
+        assert!(expires_at - OffsetDateTime::now_utc().unix_timestamp() > 3500);
+    }
+}
@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use redis::{
+    aio::{ConnectionLike, ConnectionManager},
+    AsyncCommands, Script,
+};
+use time::{Duration, OffsetDateTime};
+
+use super::{SessionRecord, SessionStore, StoreError};
+
+/// [`SessionStore`] kept in Redis, sessions expire through native key TTLs.
+///
+/// Every session is stored under `{prefix}session:{id}`, and the ids of a
+/// user's sessions are collected in the sorted set `{prefix}{user:<user_key>}`,
+/// scored by their expiry, so that [`SessionStore::delete_user`] can remove
+/// them at once.
+///
+/// The set lives as long as the longest session of the user, the ids of the
+/// expired sessions are removed from it on `save`.
+///
+/// Every script only touches the keys passed in `KEYS`, and the `{user:..}`
+/// hash tag keeps the set on one cluster slot, so a Redis Cluster works too,
+/// give `new` a `redis::cluster_async::ClusterConnection`.
+#[derive(Clone)]
+pub struct RedisStore<C = ConnectionManager> {
+    conn: C,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Connect to the redis server at `url`, e.g. `redis://127.0.0.1/`.
+    pub async fn open(url: &str) -> Result<Self, StoreError> {
+        let client = redis::Client::open(url)?;
+        Ok(Self::new(client.get_connection_manager().await?))
+    }
+}
+
+impl<C> RedisStore<C> {
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            prefix: "loginmanager:".to_owned(),
+        }
+    }
+
+    /// Set the prefix of all keys, Default: `loginmanager:`
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    fn session_key(&self, id: &str) -> String {
+        format!("{}session:{}", self.prefix, id)
+    }
+
+    fn user_key(&self, user_key: &str) -> String {
+        format!("{}{{user:{}}}", self.prefix, user_key)
+    }
+}
+
+fn seconds(ttl: Duration) -> i64 {
+    ttl.whole_seconds().max(1)
+}
+
+/// KEYS: user set; ARGV: id, ttl, now
+///
+/// Only extend the TTL of the user set, it must outlive every session in it.
+const INDEX: &str = r"
+local ttl = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+redis.call('ZADD', KEYS[1], now + ttl, ARGV[1])
+-- -1 without expiry
+if redis.call('TTL', KEYS[1]) < ttl then
+    redis.call('EXPIRE', KEYS[1], ttl)
+end
+";
+
+/// KEYS: user set; return the ids of the set, which is removed
+const TAKE: &str = r"
+local ids = redis.call('ZRANGE', KEYS[1], 0, -1)
+redis.call('DEL', KEYS[1])
+return ids
+";
+
+impl<C> RedisStore<C>
+where
+    C: ConnectionLike + Clone + Send + Sync + 'static,
+{
+    /// Add the session `id` to the set of `user_key`, it expires after `ttl`.
+    async fn index(&self, user_key: &str, id: &str, ttl: Duration) -> Result<(), StoreError> {
+        let mut conn = self.conn.clone();
+        Script::new(INDEX)
+            .key(self.user_key(user_key))
+            .arg(id)
+            .arg(seconds(ttl))
+            .arg(OffsetDateTime::now_utc().unix_timestamp())
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C> SessionStore for RedisStore<C>
+where
+    C: ConnectionLike + Clone + Send + Sync + 'static,
+{
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, StoreError> {
+        let mut conn = self.conn.clone();
+        let key = self.session_key(id);
+        let (value, ttl): (Option<String>, i64) = redis::pipe()
+            .get(&key)
+            .ttl(&key)
+            .query_async(&mut conn)
+            .await?;
+        match value {
+            Some(value) => Ok(Some(SessionRecord {
+                // -1 without expiry
+                expires_at: (ttl >= 0).then(|| OffsetDateTime::now_utc().unix_timestamp() + ttl),
+                ..serde_json::from_str(&value)?
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        id: &str,
+        record: &SessionRecord,
+        ttl: Duration,
+    ) -> Result<(), StoreError> {
+        let mut conn = self.conn.clone();
+        conn.set_ex::<_, _, ()>(
+            self.session_key(id),
+            serde_json::to_string(record)?,
+            seconds(ttl) as u64,
+        )
+        .await?;
+        if let Some(ref user_key) = record.user_key {
+            self.index(user_key, id, ttl).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), StoreError> {
+        let record = self.load(id).await?;
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(self.session_key(id)).await?;
+        if let Some(user_key) = record.and_then(|r| r.user_key) {
+            conn.zrem::<_, _, ()>(self.user_key(&user_key), id).await?;
+        }
+        Ok(())
+    }
+
+    async fn touch(&self, id: &str, ttl: Duration) -> Result<(), StoreError> {
+        let record = self.load(id).await?;
+        let mut conn = self.conn.clone();
+        let touched: bool = conn.expire(self.session_key(id), seconds(ttl)).await?;
+        if let (true, Some(user_key)) = (touched, record.and_then(|r| r.user_key)) {
+            self.index(&user_key, id, ttl).await?;
+        }
+        Ok(())
+    }
+
+    /// The set is taken at once, a session saved meanwhile is kept in a new set.
+    async fn delete_user(&self, user_key: &str) -> Result<(), StoreError> {
+        let mut conn = self.conn.clone();
+        let ids: Vec<String> = Script::new(TAKE)
+            .key(self.user_key(user_key))
+            .invoke_async(&mut conn)
+            .await?;
+        // one key per command, the sessions are on different cluster slots
+        for id in ids {
+            conn.del::<_, ()>(self.session_key(&id)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run with `REDIS_URL=redis://127.0.0.1/`, skipped without it.
+    #[tokio::test]
+    async fn user_set() {
+        let url = match std::env::var("REDIS_URL") {
+            Ok(url) => url,
+            Err(_) => return eprintln!("REDIS_URL is not set, skipped"),
+        };
+        let store = RedisStore::open(&url)
+            .await
+            .unwrap()
+            .prefix(&format!("loginmanager-test-{}:", super::super::random_id()));
+        let mut conn = store.conn.clone();
+        let record = SessionRecord {
+            user_key: Some("1".to_owned()),
+            ..Default::default()
+        };
+        let set = store.user_key("1");
+
+        store.save("a", &record, Duration::hours(1)).await.unwrap();
+        store
+            .save("b", &record, Duration::minutes(1))
+            .await
+            .unwrap();
+        let ttl: i64 = conn.ttl(&set).await.unwrap();
+        assert!(ttl > 3500, "a shorter session shortened the set: {ttl}");
+        store.touch("b", Duration::seconds(30)).await.unwrap();
+        let ttl: i64 = conn.ttl(&set).await.unwrap();
+        assert!(ttl > 3500, "a touch shortened the set: {ttl}");
+
+        // the expired ids are removed on save
+        let expired = OffsetDateTime::now_utc().unix_timestamp() - 1;
+        let _: () = conn.zadd(&set, "b", expired).await.unwrap();
+        store.save("c", &record, Duration::hours(1)).await.unwrap();
+        let ids: Vec<String> = conn.zrange(&set, 0, -1).await.unwrap();
+        assert_eq!(ids, ["a", "c"]);
+
+        let loaded = store.load("a").await.unwrap().unwrap();
+        assert_eq!(loaded.user_key.as_deref(), Some("1"));
+        assert!(loaded.expires_at.is_some());
+
+        store.delete("a").await.unwrap();
+        let ids: Vec<String> = conn.zrange(&set, 0, -1).await.unwrap();
+        assert_eq!(ids, ["c"]);
+
+        store.delete_user("1").await.unwrap();
+        assert_eq!(store.load("c").await.unwrap(), None);
+        let exists: bool = conn.exists(&set).await.unwrap();
+        assert!(!exists);
+    }
+}
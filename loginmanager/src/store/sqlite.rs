@@ -0,0 +1,187 @@
+use async_trait::async_trait;
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, Value};
+use time::{Duration, OffsetDateTime};
+
+use super::{SessionRecord, SessionStore, StoreError};
+
+/// [`SessionStore`] persisted in a SQLite database through sea-orm.
+///
+/// Call [`SqliteStore::migrate`] once to create the table, expired rows are
+/// ignored on load and removed by [`SqliteStore::cleanup`].
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: DatabaseConnection,
+    table: String,
+}
+
+/// Quote an identifier of the SQL statements.
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+impl SqliteStore {
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self {
+            conn,
+            table: "loginmanager_session".to_owned(),
+        }
+    }
+
+    /// Set the table name, Default: `loginmanager_session`
+    pub fn table_name(mut self, table: &str) -> Self {
+        self.table = table.to_owned();
+        self
+    }
+
+    fn statement(&self, sql: &str, values: Vec<Value>) -> Statement {
+        let sql = sql.replace("{table}", &quote(&self.table));
+        Statement::from_sql_and_values(DatabaseBackend::Sqlite, &sql, values)
+    }
+
+    /// Create the session table if it does not exist.
+    pub async fn migrate(&self) -> Result<(), StoreError> {
+        self.conn
+            .execute(self.statement(
+                r#"CREATE TABLE IF NOT EXISTS {table} (
+                    "id" TEXT NOT NULL PRIMARY KEY,
+                    "user_key" TEXT,
+                    "record" TEXT NOT NULL,
+                    "expires_at" INTEGER NOT NULL
+                )"#,
+                vec![],
+            ))
+            .await?;
+        for column in ["expires_at", "user_key"] {
+            let index = quote(&format!("{}_{column}", self.table));
+            self.conn
+                .execute(self.statement(
+                    &format!(r#"CREATE INDEX IF NOT EXISTS {index} ON {{table}} ("{column}")"#),
+                    vec![],
+                ))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Delete the expired sessions, return the number of deleted rows.
+    pub async fn cleanup(&self) -> Result<u64, StoreError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let res = self
+            .conn
+            .execute(self.statement(
+                "DELETE FROM {table} WHERE expires_at <= ?",
+                vec![now.into()],
+            ))
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Run [`SqliteStore::cleanup`] every `period` on the tokio runtime.
+    pub fn spawn_cleanup(&self, period: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let _ = store.cleanup().await;
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqliteStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, StoreError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let row = self
+            .conn
+            .query_one(self.statement(
+                "SELECT record, expires_at FROM {table} WHERE id = ? AND expires_at > ?",
+                vec![id.into(), now.into()],
+            ))
+            .await?;
+        match row {
+            Some(row) => {
+                let record = row.try_get::<String>("", "record")?;
+                Ok(Some(SessionRecord {
+                    expires_at: Some(row.try_get::<i64>("", "expires_at")?),
+                    ..serde_json::from_str(&record)?
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        id: &str,
+        record: &SessionRecord,
+        ttl: Duration,
+    ) -> Result<(), StoreError> {
+        let expires_at = (OffsetDateTime::now_utc() + ttl).unix_timestamp();
+        self.conn
+            .execute(self.statement(
+                "INSERT OR REPLACE INTO {table} (id, user_key, record, expires_at) VALUES (?, ?, ?, ?)",
+                vec![
+                    id.into(),
+                    record.user_key.clone().into(),
+                    serde_json::to_string(record)?.into(),
+                    expires_at.into(),
+                ],
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), StoreError> {
+        self.conn
+            .execute(self.statement("DELETE FROM {table} WHERE id = ?", vec![id.into()]))
+            .await?;
+        Ok(())
+    }
+
+    async fn touch(&self, id: &str, ttl: Duration) -> Result<(), StoreError> {
+        let expires_at = (OffsetDateTime::now_utc() + ttl).unix_timestamp();
+        self.conn
+            .execute(self.statement(
+                "UPDATE {table} SET expires_at = ? WHERE id = ?",
+                vec![expires_at.into(), id.into()],
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_user(&self, user_key: &str) -> Result<(), StoreError> {
+        self.conn
+            .execute(self.statement(
+                "DELETE FROM {table} WHERE user_key = ?",
+                vec![user_key.into()],
+            ))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::Database;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn quoted_table_name() {
+        let conn = Database::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteStore::new(conn).table_name(r#"my "sessions"; --"#);
+        store.migrate().await.unwrap();
+        store.migrate().await.unwrap();
+        let record = SessionRecord {
+            user_key: Some("1".to_owned()),
+            ..Default::default()
+        };
+        store.save("a", &record, Duration::hours(1)).await.unwrap();
+        let loaded = store.load("a").await.unwrap().unwrap();
+        assert_eq!(loaded.user_key.as_deref(), Some("1"));
+        store.delete_user("1").await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::channel::oneshot;
+use futures_util::future::{FutureExt, Shared};
+use serde::Serialize;
+
+use crate::{LoginError, UserMinix};
+
+/// The type of the user and the key string of `LoginInfo`.
+type CacheKey = (TypeId, String);
+
+type CachedUser = Arc<dyn Any + Send + Sync>;
+
+type Waiter = Shared<oneshot::Receiver<Result<Option<CachedUser>, LoginError>>>;
+
+struct Entry {
+    user: CachedUser,
+    expires: Instant,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<CacheKey, Entry>,
+    /// The keys by their last use, the oldest first.
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    /// Bumped by `invalidate`, a load started before it is not kept nor shared.
+    epoch: u64,
+    loading: HashMap<CacheKey, Waiter>,
+}
+
+impl Inner {
+    fn get(&mut self, key: &CacheKey) -> Option<CachedUser> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires <= Instant::now() {
+            let tick = entry.tick;
+            self.entries.remove(key);
+            self.lru.remove(&tick);
+            return None;
+        }
+        self.tick += 1;
+        self.lru.remove(&entry.tick);
+        self.lru.insert(self.tick, key.clone());
+        entry.tick = self.tick;
+        Some(entry.user.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, user: CachedUser, ttl: Duration, capacity: usize) {
+        self.tick += 1;
+        self.lru.insert(self.tick, key.clone());
+        let entry = Entry {
+            user,
+            expires: Instant::now() + ttl,
+            tick: self.tick,
+        };
+        if let Some(old) = self.entries.insert(key, entry) {
+            self.lru.remove(&old.tick);
+        }
+        while self.entries.len() > capacity {
+            match self.lru.pop_first() {
+                Some((_, key)) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    fn remove_if<F: Fn(&CacheKey) -> bool>(&mut self, f: F) {
+        self.entries.retain(|key, _| !f(key));
+        self.lru.retain(|_, key| !f(key));
+        self.epoch += 1;
+    }
+}
+
+/// The loaded users shared between requests, see `LoginManager::user_cache`.
+///
+/// A bounded LRU with a TTL, concurrent loads of the same user are made once.
+/// Keep a clone to `invalidate` a user after it is updated, the
+/// `UserMinix::session_epoch` is checked against the cached user too.
+///
+/// ## Example
+/// ```
+/// use std::time::Duration;
+///
+/// use loginmanager::{CookieSession, LoginManager, UserCache};
+///
+/// let cache = UserCache::new(1000).ttl(Duration::from_secs(30));
+/// let loginmanager = LoginManager::new(CookieSession::new("secret")).user_cache(cache.clone());
+///
+/// // after the profile of the user 1 is updated
+/// cache.invalidate(&1);
+/// ```
+#[derive(Clone)]
+pub struct UserCache {
+    inner: Arc<Mutex<Inner>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl UserCache {
+    /// Keep at most `capacity` users, for 60 seconds by default.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Default::default(),
+            capacity,
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    /// Set how long a user is kept after it is loaded.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Drop the user of `key`, the same as `UserMinix::get_id`.
+    pub fn invalidate<K: Serialize>(&self, key: &K) {
+        if let Ok(key) = serde_json::to_string(key) {
+            self.inner.lock().unwrap().remove_if(|(_, k)| *k == key);
+        }
+    }
+
+    /// Drop all users.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().remove_if(|_| true);
+    }
+
+    /// The cached user of `key`, or the result of `load` shared with the
+    /// requests loading the same user meanwhile. If `invalidate` is called
+    /// during the load, the others load the user again.
+    pub(crate) async fn get_or_load<U, F>(
+        &self,
+        key: &str,
+        load: F,
+    ) -> Result<Option<U>, LoginError>
+    where
+        U: UserMinix,
+        F: Future<Output = Result<Option<U>, LoginError>>,
+    {
+        let key = (TypeId::of::<U>(), key.to_owned());
+        let downcast = |user: CachedUser| user.downcast_ref::<U>().cloned();
+        let (sender, epoch) = loop {
+            let waiter = {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(user) = inner.get(&key) {
+                    return Ok(downcast(user));
+                }
+                match inner.loading.get(&key) {
+                    Some(waiter) => waiter.clone(),
+                    None => {
+                        let (sender, receiver) = oneshot::channel();
+                        inner.loading.insert(key.clone(), receiver.shared());
+                        break (sender, inner.epoch);
+                    }
+                }
+            };
+            // try again if the other load is dropped or made stale by `invalidate`
+            if let Ok(result) = waiter.await {
+                return result.map(|user| user.and_then(downcast));
+            }
+        };
+
+        let mut loading = Loading {
+            cache: self,
+            key: Some(key),
+        };
+        let result = load.await;
+        let shared = result
+            .clone()
+            .map(|user| user.map(|user| Arc::new(user) as CachedUser));
+        if let Some(key) = loading.key.take() {
+            let mut inner = self.inner.lock().unwrap();
+            inner.loading.remove(&key);
+            // a stale user is neither kept nor shared, the sender is dropped
+            if inner.epoch == epoch {
+                if let Ok(Some(ref user)) = shared {
+                    inner.insert(key, user.clone(), self.ttl, self.capacity);
+                }
+                let _ = sender.send(shared);
+            }
+        }
+        result
+    }
+}
+
+/// Stop waiting for a load which is dropped, e.g. the client is gone.
+struct Loading<'a> {
+    cache: &'a UserCache,
+    key: Option<CacheKey>,
+}
+
+impl Drop for Loading<'_> {
+    fn drop(&mut self) {
+        if let Some(ref key) = self.key {
+            self.cache.inner.lock().unwrap().loading.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::LoaderError;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct User {
+        id: i32,
+        name: &'static str,
+    }
+
+    #[async_trait]
+    impl UserMinix for User {
+        type Key = i32;
+        type State = ();
+
+        async fn load_user(_: &i32, _: &()) -> Result<Option<Self>, LoaderError> {
+            Ok(None)
+        }
+
+        fn get_id(&self) -> &i32 {
+            &self.id
+        }
+    }
+
+    /// Load the user `id` through the cache, counting the calls of the loader.
+    async fn load(cache: &UserCache, id: i32, calls: &AtomicUsize) -> Option<User> {
+        let load = async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(Some(User { id, name: "user" }))
+        };
+        cache.get_or_load(&id.to_string(), load).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn single_flight() {
+        let cache = UserCache::new(10);
+        let calls = AtomicUsize::new(0);
+        let users = futures::future::join_all((0..16).map(|_| load(&cache, 1, &calls))).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(users
+            .iter()
+            .all(|user| user.as_ref().map(|u| u.id) == Some(1)));
+
+        load(&cache, 1, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn eviction_order() {
+        let cache = UserCache::new(2);
+        let calls = AtomicUsize::new(0);
+        load(&cache, 1, &calls).await;
+        load(&cache, 2, &calls).await;
+        // 1 is used again, so 2 is the oldest
+        load(&cache, 1, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        load(&cache, 3, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        load(&cache, 1, &calls).await;
+        load(&cache, 3, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        load(&cache, 2, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn ttl_expiry() {
+        let cache = UserCache::new(10).ttl(Duration::from_millis(50));
+        let calls = AtomicUsize::new(0);
+        load(&cache, 1, &calls).await;
+        load(&cache, 1, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        load(&cache, 1, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn invalidate_during_load() {
+        let cache = UserCache::new(10);
+        let (release, released) = oneshot::channel::<()>();
+        let old = async {
+            released.await.unwrap();
+            Ok(Some(User { id: 1, name: "old" }))
+        };
+        let new = || async { Ok(Some(User { id: 1, name: "new" })) };
+
+        // polled in order: the first load starts, a waiter joins it, the user
+        // is invalidated, another waiter joins, then the first load ends
+        let (first, before, after, _) = futures::join!(
+            cache.get_or_load("1", old),
+            cache.get_or_load("1", new()),
+            async {
+                cache.invalidate(&1);
+                cache.get_or_load("1", new()).await
+            },
+            async { release.send(()).unwrap() },
+        );
+        assert_eq!(first.unwrap().unwrap().name, "old");
+        assert_eq!(before.unwrap().unwrap().name, "new");
+        assert_eq!(after.unwrap().unwrap().name, "new");
+
+        let cached = cache.get_or_load::<User, _>("1", async { Ok(None) }).await;
+        assert_eq!(cached.unwrap().unwrap().name, "new");
+    }
+}
@@ -0,0 +1,35 @@
+use std::{future::Future, sync::Arc};
+
+use futures_util::future::BoxFuture;
+
+use crate::{LoaderError, UserMinix};
+
+pub(crate) type LoadFuture<U> = BoxFuture<'static, Result<Option<U>, LoaderError>>;
+
+type LoadFn<U> =
+    Arc<dyn Fn(<U as UserMinix>::Key, &<U as UserMinix>::State) -> LoadFuture<U> + Send + Sync>;
+
+/// The closure of `LoginManager::user_loader`, used instead of `UserMinix::load_user`.
+///
+/// It takes the `UserMinix::State`, found by the extractors like for
+/// `load_user`, so the state is checked by the compiler with axum.
+pub(crate) struct UserLoader<U: UserMinix> {
+    load: LoadFn<U>,
+}
+
+impl<U: UserMinix> UserLoader<U> {
+    pub(crate) fn new<F, Fut>(loader: F) -> Self
+    where
+        U::State: Clone,
+        F: Fn(U::Key, U::State) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<U>, LoaderError>> + Send + 'static,
+    {
+        Self {
+            load: Arc::new(move |key, state| Box::pin(loader(key, state.clone()))),
+        }
+    }
+
+    pub(crate) fn load(&self, key: U::Key, state: &U::State) -> LoadFuture<U> {
+        (self.load)(key, state)
+    }
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use axum::{routing::get, Extension, Router};
+use loginmanager::{
+    ApiKey, ApiKeyRecord, ApiKeyStore, AuthUser, LoginInfo, LoginManager, StoreError,
+};
+
+use common::User;
+
+fn key() -> &'static str {
+    static KEY: OnceLock<String> = OnceLock::new();
+    KEY.get_or_init(ApiKey::generate)
+}
+
+/// A record of user 2 whose hash only shares the prefix with the key, found first.
+fn other() -> ApiKeyRecord {
+    let mut hash = ApiKey::hash(key());
+    hash.replace_range(hash.len() - 1.., "x");
+    ApiKeyRecord::new(hash, &2, vec!["write".to_owned()]).unwrap()
+}
+
+/// The key of user 1 next to [`other`].
+struct Keys;
+
+#[async_trait]
+impl ApiKeyStore for Keys {
+    async fn find(&self, hash_prefix: &str) -> Result<Vec<ApiKeyRecord>, StoreError> {
+        let record = ApiKeyRecord::new(ApiKey::hash(key()), &1, vec!["read".to_owned()])?;
+        Ok([other(), record]
+            .into_iter()
+            .filter(|record| record.hash.starts_with(hash_prefix))
+            .collect())
+    }
+}
+
+/// Only [`other`].
+struct Other;
+
+#[async_trait]
+impl ApiKeyStore for Other {
+    async fn find(&self, hash_prefix: &str) -> Result<Vec<ApiKeyRecord>, StoreError> {
+        assert_eq!(hash_prefix, ApiKey::hash_prefix(&ApiKey::hash(key())));
+        Ok(vec![other()])
+    }
+}
+
+struct Broken;
+
+#[async_trait]
+impl ApiKeyStore for Broken {
+    async fn find(&self, _: &str) -> Result<Vec<ApiKeyRecord>, StoreError> {
+        Err("connection refused".into())
+    }
+}
+
+fn routes() -> Router {
+    let me = |AuthUser(user): AuthUser<User>, Extension(info): Extension<LoginInfo>| async move {
+        format!("{} {}", user.id, info.scopes().join(","))
+    };
+    Router::new().route("/me", get(me))
+}
+
+#[tokio::test]
+async fn header_lookup() {
+    let app = routes().layer(LoginManager::new(ApiKey::new(Keys)));
+    let res = common::get(&app, "/me", &[("x-api-key", key())]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "1 read");
+
+    // not read from the query by default
+    let res = common::get(&app, &format!("/me?api_key={}", key()), &[]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn query_lookup() {
+    let app = routes().layer(LoginManager::new(
+        ApiKey::new(Keys).header(None).query(Some("api_key")),
+    ));
+    let res = common::get(&app, &format!("/me?a=1&api_key={}", key()), &[]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "1 read");
+
+    let res = common::get(&app, "/me", &[("x-api-key", key())]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn hash_mismatch() {
+    let app = routes().layer(LoginManager::new(ApiKey::new(Keys)));
+    let res = common::get(&app, "/me", &[("x-api-key", &ApiKey::generate())]).await;
+    assert_eq!(res.status, 401);
+
+    // the prefix is the same, the full hash is not
+    let app = routes().layer(LoginManager::new(ApiKey::new(Other)));
+    let res = common::get(&app, "/me", &[("x-api-key", key())]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn store_error() {
+    let app = routes().layer(LoginManager::new(ApiKey::new(Broken)));
+    let res = common::get(&app, "/me", &[("x-api-key", key())]).await;
+    assert_eq!(res.status, 500);
+    // no key, the store is not asked
+    let res = common::get(&app, "/me", &[]).await;
+    assert_eq!(res.status, 401);
+}
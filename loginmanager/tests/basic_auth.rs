@@ -0,0 +1,59 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use async_trait::async_trait;
+use axum::{routing::get, Router};
+use loginmanager::{AuthUser, BasicAuth, BasicVerifier, CookieSession, FirstOf, LoginManager};
+
+use common::User;
+
+struct Verifier;
+
+#[async_trait]
+impl BasicVerifier for Verifier {
+    type Key = i32;
+
+    async fn verify(&self, username: &str, password: &str) -> Option<i32> {
+        (username == "admin" && password == "secret").then_some(1)
+    }
+}
+
+fn routes() -> Router {
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    Router::new().route("/me", get(me))
+}
+
+const HTML: (&str, &str) = ("accept", "text/html");
+// admin:wrong
+const WRONG: (&str, &str) = ("authorization", "Basic YWRtaW46d3Jvbmc=");
+// admin:secret
+const RIGHT: (&str, &str) = ("authorization", "Basic YWRtaW46c2VjcmV0");
+
+#[tokio::test]
+async fn alone_challenges() {
+    let app = routes().layer(LoginManager::new(BasicAuth::new(Verifier)));
+    let res = common::get(&app, "/me", &[HTML]).await;
+    assert_eq!(res.status, 401);
+    assert!(res.headers.contains_key("www-authenticate"));
+    let res = common::get(&app, "/me", &[RIGHT]).await;
+    assert_eq!(res.body, "1");
+}
+
+#[tokio::test]
+async fn next_to_session_redirects_browsers() {
+    let app = routes().layer(LoginManager::new(FirstOf((
+        CookieSession::new("secret"),
+        BasicAuth::new(Verifier),
+    ))));
+    let res = common::get(&app, "/me", &[HTML]).await;
+    assert_eq!(res.status, 303);
+    assert!(!res.headers.contains_key("www-authenticate"));
+
+    let res = common::get(&app, "/me", &[HTML, WRONG]).await;
+    assert_eq!(res.status, 401);
+    assert!(res.headers.contains_key("www-authenticate"));
+
+    let res = common::get(&app, "/me", &[RIGHT]).await;
+    assert_eq!(res.body, "1");
+}
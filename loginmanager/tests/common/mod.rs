@@ -0,0 +1,97 @@
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use axum::{body::Body, http::Request, Router};
+use loginmanager::{LoaderError, UserMinix};
+use tower::ServiceExt;
+
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: i32,
+}
+
+#[async_trait]
+impl UserMinix for User {
+    type Key = i32;
+    type State = ();
+
+    async fn load_user(id: &i32, _: &()) -> Result<Option<Self>, LoaderError> {
+        Ok(Some(User { id: *id }))
+    }
+
+    fn get_id(&self) -> &i32 {
+        &self.id
+    }
+}
+
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: http::HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    /// The `Set-Cookie` values as `name=value`, without the attributes.
+    pub fn set_cookies(&self) -> Vec<String> {
+        self.headers
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().split(';').next().unwrap().to_owned())
+            .collect()
+    }
+
+    /// Apply the `Set-Cookie` of the response to the `Cookie` header `jar`.
+    pub fn jar(&self, jar: &str) -> String {
+        let mut cookies: Vec<(String, String)> = jar
+            .split("; ")
+            .filter_map(|c| c.split_once('='))
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        for cookie in self.set_cookies() {
+            let (name, value) = cookie.split_once('=').unwrap();
+            cookies.retain(|(k, _)| k != name);
+            if !value.is_empty() {
+                cookies.push((name.to_owned(), value.to_owned()));
+            }
+        }
+        cookies
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+pub async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> TestResponse {
+    let mut builder = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    let req = builder.body(Body::from(body.to_owned())).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status().as_u16();
+    let headers = res.headers().clone();
+    let body = http_body_util::BodyExt::collect(res.into_body())
+        .await
+        .unwrap()
+        .to_bytes();
+    TestResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }
+}
+
+pub async fn get(app: &Router, uri: &str, headers: &[(&str, &str)]) -> TestResponse {
+    send(app, "GET", uri, headers, "").await
+}
+
+pub async fn post(app: &Router, uri: &str, headers: &[(&str, &str)], body: &str) -> TestResponse {
+    send(app, "POST", uri, headers, body).await
+}
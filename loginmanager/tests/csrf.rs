@@ -0,0 +1,60 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use loginmanager::{CookieSession, CsrfProtection, CsrfToken, LoginManager};
+
+fn app(loginmanager: LoginManager<CookieSession>) -> Router {
+    let form = |csrf_token: CsrfToken| async move { csrf_token.0 };
+    Router::new()
+        .route("/form", get(form))
+        .route("/submit", post(|| async { "ok" }))
+        .layer(CsrfProtection::new())
+        .layer(loginmanager)
+}
+
+const ORIGIN: (&str, &str) = ("origin", "http://example.com");
+const HOST: (&str, &str) = ("host", "example.com");
+
+#[tokio::test]
+async fn token_required_once_issued() {
+    let app = app(LoginManager::new(CookieSession::new("secret")));
+    // no token in the session yet, the same origin passes
+    let res = common::post(&app, "/submit", &[ORIGIN, HOST], "").await;
+    assert_eq!(res.status, 200);
+    let res = common::post(&app, "/submit", &[("origin", "http://evil.com"), HOST], "").await;
+    assert_eq!(res.status, 403);
+
+    let res = common::get(&app, "/form", &[HOST]).await;
+    let (token, jar) = (res.body.clone(), res.jar(""));
+    let res = common::post(&app, "/submit", &[ORIGIN, HOST, ("cookie", &jar)], "").await;
+    assert_eq!(res.status, 403);
+    assert_eq!(res.body, "CSRF token missing or incorrect.");
+
+    let headers = [HOST, ("cookie", &jar), ("x-csrf-token", &token)];
+    let res = common::post(&app, "/submit", &headers, "").await;
+    assert_eq!(res.status, 200);
+    let headers = [
+        HOST,
+        ("cookie", &jar),
+        ("content-type", "application/x-www-form-urlencoded"),
+    ];
+    let res = common::post(&app, "/submit", &headers, &format!("csrf_token={token}")).await;
+    assert_eq!(res.status, 200);
+}
+
+#[tokio::test]
+async fn rendered_as_login_error() {
+    let app = app(LoginManager::new(CookieSession::new("secret")).problem_json());
+    let res = common::post(&app, "/submit", &[("origin", "http://evil.com"), HOST], "").await;
+    assert_eq!(res.status, 403);
+    assert_eq!(
+        res.headers["content-type"].to_str().unwrap(),
+        "application/problem+json"
+    );
+    assert!(res.body.contains("CSRF token missing or incorrect."));
+}
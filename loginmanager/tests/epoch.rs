@@ -0,0 +1,176 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use axum::{routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, LoaderError, LoginManager, LoginOptions, MemoryStore,
+    RememberMe, SessionData, UserMinix,
+};
+
+/// The `session_epoch` of the users, each test uses its own ids.
+static EPOCHS: Mutex<Option<HashMap<i32, u32>>> = Mutex::new(None);
+
+fn set_epoch(id: i32, epoch: Option<u32>) {
+    let mut epochs = EPOCHS.lock().unwrap();
+    let epochs = epochs.get_or_insert_with(HashMap::new);
+    match epoch {
+        Some(epoch) => epochs.insert(id, epoch),
+        None => epochs.remove(&id),
+    };
+}
+
+#[derive(Clone)]
+struct User {
+    id: i32,
+}
+
+#[async_trait]
+impl UserMinix for User {
+    type Key = i32;
+    type State = ();
+
+    async fn load_user(id: &i32, _: &()) -> Result<Option<Self>, LoaderError> {
+        Ok(Some(User { id: *id }))
+    }
+
+    fn get_id(&self) -> &i32 {
+        &self.id
+    }
+
+    fn session_epoch(&self) -> Option<String> {
+        let epochs = EPOCHS.lock().unwrap();
+        epochs.as_ref()?.get(&self.id).map(u32::to_string)
+    }
+}
+
+fn app(store: &MemoryStore) -> Router {
+    let login = |mut auth_context: AuthContext, uri: axum::http::Uri| async move {
+        let id = uri.query().unwrap().parse().unwrap();
+        auth_context.login_with(&User { id }, LoginOptions { remember: true });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let clear = |mut data: SessionData| async move {
+        data.clear();
+        "ok"
+    };
+    Router::new()
+        .route("/login", get(login))
+        .route("/me", get(me))
+        .route("/clear", get(clear))
+        .layer(LoginManager::new(RememberMe::new(
+            CookieSession::new("secret"),
+            store.clone(),
+        )))
+}
+
+/// The cookies of the jar named `name`.
+fn only(jar: &str, name: &str) -> String {
+    jar.split("; ")
+        .filter(|c| c.starts_with(&format!("{name}=")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[tokio::test]
+async fn cookie_session() {
+    let app = app(&MemoryStore::new());
+    set_epoch(1, Some(0));
+    let res = common::get(&app, "/login?1", &[]).await;
+    let session = only(&res.jar(""), "_session");
+    let res = common::get(&app, "/me", &[("cookie", &session)]).await;
+    assert_eq!(res.status, 200);
+
+    set_epoch(1, Some(1));
+    let res = common::get(&app, "/me", &[("cookie", &session)]).await;
+    assert_eq!(res.status, 401);
+
+    // a session made before the user had an epoch
+    set_epoch(2, None);
+    let res = common::get(&app, "/login?2", &[]).await;
+    let session = only(&res.jar(""), "_session");
+    let res = common::get(&app, "/me", &[("cookie", &session)]).await;
+    assert_eq!(res.status, 200);
+    set_epoch(2, Some(0));
+    let res = common::get(&app, "/me", &[("cookie", &session)]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn remember_session() {
+    let store = MemoryStore::new();
+    let app = app(&store);
+    set_epoch(11, Some(0));
+    let res = common::get(&app, "/login?11", &[]).await;
+    let remember = only(&res.jar(""), "_remember");
+
+    // restored without the session cookie
+    let res = common::get(&app, "/me", &[("cookie", &remember)]).await;
+    assert_eq!(res.status, 200);
+    let remember = only(&res.jar(&remember), "_remember");
+
+    set_epoch(11, Some(1));
+    let res = common::get(&app, "/me", &[("cookie", &remember)]).await;
+    assert_eq!(res.status, 401);
+    // the series is revoked
+    assert!(res.set_cookies().contains(&"_remember=".to_owned()));
+    assert!(store.sessions().is_empty());
+    let res = common::get(&app, "/me", &[("cookie", &remember)]).await;
+    assert_eq!(res.status, 401);
+
+    // a series issued before the user had an epoch
+    set_epoch(12, None);
+    let res = common::get(&app, "/login?12", &[]).await;
+    let remember = only(&res.jar(""), "_remember");
+    set_epoch(12, Some(0));
+    let res = common::get(&app, "/me", &[("cookie", &remember)]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn session_data_cleared() {
+    let app = app(&MemoryStore::new());
+    set_epoch(21, Some(0));
+    let res = common::get(&app, "/login?21", &[]).await;
+    let session = only(&res.jar(""), "_session");
+    let res = common::get(&app, "/clear", &[("cookie", &session)]).await;
+    let session = only(&res.jar(&session), "_session");
+    let res = common::get(&app, "/me", &[("cookie", &session)]).await;
+    assert_eq!(res.status, 200);
+}
+
+#[cfg(feature = "jwt")]
+#[tokio::test]
+async fn bearer_with_anonymous_session() {
+    use loginmanager::{FirstOf, JwtBearer};
+
+    let jwt = JwtBearer::hs256(b"secret");
+    let token = format!("Bearer {}", jwt.mint("31").unwrap());
+    let visit = |mut data: SessionData| async move {
+        data.insert("locale", &"en").unwrap();
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let app = Router::new()
+        .route("/visit", get(visit))
+        .route("/me", get(me))
+        .layer(LoginManager::new(FirstOf((
+            CookieSession::new("secret"),
+            jwt,
+        ))));
+    set_epoch(31, Some(0));
+
+    let res = common::get(&app, "/visit", &[]).await;
+    let session = only(&res.jar(""), "_session");
+    assert!(!session.is_empty());
+    let res = common::get(&app, "/me", &[("authorization", &token)]).await;
+    assert_eq!(res.status, 200);
+    let headers = [("authorization", token.as_str()), ("cookie", &session)];
+    let res = common::get(&app, "/me", &headers).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "31");
+}
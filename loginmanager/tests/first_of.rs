@@ -0,0 +1,99 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, FirstOf, LoginManager, MemoryStore, StoreSession,
+};
+
+use common::User;
+
+fn routes() -> Router {
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login(&User { id: 1 });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login", get(login))
+        .route("/me", get(me))
+}
+
+#[cfg(feature = "jwt")]
+#[tokio::test]
+async fn login_with_one_session_decoder() {
+    let app = routes().layer(LoginManager::new(FirstOf((
+        CookieSession::new("secret"),
+        loginmanager::JwtBearer::hs256(b"secret"),
+    ))));
+    let res = common::get(&app, "/login", &[]).await;
+    assert_eq!(res.set_cookies().len(), 1);
+    let res = common::get(&app, "/me", &[("cookie", &res.jar(""))]).await;
+    assert_eq!(res.body, "1");
+}
+
+#[tokio::test]
+async fn login_with_first_session_decoder() {
+    let store = MemoryStore::new();
+    let app = routes().layer(LoginManager::new(FirstOf((
+        StoreSession::new(store.clone()),
+        CookieSession::new("secret"),
+    ))));
+    let res = common::get(&app, "/login", &[]).await;
+    let cookies = res.set_cookies();
+    assert_eq!(cookies.len(), 1);
+    assert!(cookies[0].starts_with("_session_id="));
+    assert_eq!(store.sessions().len(), 1);
+    let res = common::get(&app, "/me", &[("cookie", &res.jar(""))]).await;
+    assert_eq!(res.body, "1");
+}
+
+#[cfg(feature = "jwt")]
+#[tokio::test]
+async fn bearer_keeps_anonymous_session_data() {
+    use loginmanager::{JwtBearer, SessionData};
+
+    let jwt = JwtBearer::hs256(b"secret");
+    let token = format!("Bearer {}", jwt.mint("1").unwrap());
+    let set = |mut data: SessionData| async move {
+        data.insert("locale", &"en").unwrap();
+        "ok"
+    };
+    let locale =
+        |data: SessionData| async move { data.get::<String>("locale").unwrap_or_default() };
+    let app = routes()
+        .route("/set", get(set))
+        .route("/locale", get(locale));
+    let store = MemoryStore::new();
+    for app in [
+        app.clone().layer(LoginManager::new(FirstOf((
+            CookieSession::new("secret"),
+            jwt.clone(),
+        )))),
+        app.layer(LoginManager::new(FirstOf((
+            StoreSession::new(store.clone()),
+            jwt.clone(),
+        )))),
+    ] {
+        let res = common::get(&app, "/set", &[("authorization", &token)]).await;
+        assert_eq!(res.status, 200);
+        let jar = res.jar("");
+        assert!(!jar.is_empty());
+
+        let res = common::get(&app, "/locale", &[("cookie", &jar)]).await;
+        assert_eq!(res.body, "en");
+        // the user of the token is not written to the session
+        let res = common::get(&app, "/me", &[("cookie", &jar)]).await;
+        assert_eq!(res.status, 401);
+
+        // the data of the session read with the token
+        let res = common::get(
+            &app,
+            "/locale",
+            &[("authorization", &token), ("cookie", &jar)],
+        )
+        .await;
+        assert_eq!(res.body, "en");
+    }
+}
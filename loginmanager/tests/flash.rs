@@ -0,0 +1,90 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, Flash, IncomingFlashes, Level, LoginManager,
+};
+
+use common::User;
+
+fn app(loginmanager: LoginManager<CookieSession>) -> Router {
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login(&User { id: 1 });
+        "ok"
+    };
+    let logout = |mut auth_context: AuthContext, mut flash: Flash| async move {
+        auth_context.logout();
+        flash.info("You have been logged out.");
+        "ok"
+    };
+    let wrong = |mut flash: Flash| async move {
+        flash.error("Wrong password.");
+        flash.warning("2 attempts left.");
+        "ok"
+    };
+    let messages = |flashes: IncomingFlashes| async move {
+        flashes
+            .into_iter()
+            .map(|flash| format!("{:?}:{}", flash.level, flash.message))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login", get(login))
+        .route("/logout", get(logout))
+        .route("/wrong", get(wrong))
+        .route("/messages", get(messages))
+        .route("/me", get(me))
+        .layer(loginmanager)
+}
+
+/// Send a request with the cookies of `jar`, return the body and the new jar.
+async fn visit(app: &Router, uri: &str, jar: &str) -> (String, String) {
+    let res = common::get(app, uri, &[("cookie", jar)]).await;
+    (res.body.clone(), res.jar(jar))
+}
+
+#[tokio::test]
+async fn consumed_after_one_read() {
+    let app = app(LoginManager::new(CookieSession::new("secret")));
+    let (_, jar) = visit(&app, "/wrong", "").await;
+    let (body, jar) = visit(&app, "/messages", &jar).await;
+    assert_eq!(body, "Error:Wrong password.|Warning:2 attempts left.");
+    let (body, _) = visit(&app, "/messages", &jar).await;
+    assert_eq!(body, "");
+}
+
+#[tokio::test]
+async fn survives_logout() {
+    let app = app(LoginManager::new(CookieSession::new("secret")));
+    let (_, jar) = visit(&app, "/login", "").await;
+    let (_, jar) = visit(&app, "/logout", &jar).await;
+    assert_eq!(
+        common::get(&app, "/me", &[("cookie", &jar)]).await.status,
+        401
+    );
+    let (body, _) = visit(&app, "/messages", &jar).await;
+    assert_eq!(body, "Info:You have been logged out.");
+}
+
+#[tokio::test]
+async fn login_message() {
+    let app = app(LoginManager::new(CookieSession::new("secret"))
+        .login_message("Please log in.")
+        .login_message_level(Level::Warning));
+    let res = common::get(&app, "/me", &[("accept", "text/html")]).await;
+    assert_eq!(res.status, 303);
+    let (body, jar) = visit(&app, "/messages", &res.jar("")).await;
+    assert_eq!(body, "Warning:Please log in.");
+    let (body, _) = visit(&app, "/messages", &jar).await;
+    assert_eq!(body, "");
+
+    // API clients get JSON, no message
+    let res = common::get(&app, "/me", &[]).await;
+    assert_eq!(res.status, 401);
+    let (body, _) = visit(&app, "/messages", &res.jar("")).await;
+    assert_eq!(body, "");
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, FreshUser, LoginManager, LoginOptions, MemoryStore,
+    RememberMe,
+};
+
+use common::User;
+
+fn app(loginmanager: LoginManager<RememberMe<CookieSession, MemoryStore>>) -> Router {
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login_with(&User { id: 1 }, LoginOptions { remember: true });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let settings = |FreshUser(user): FreshUser<User>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login", get(login))
+        .route("/me", get(me))
+        .route("/settings", get(settings))
+        .layer(loginmanager)
+}
+
+fn manager() -> LoginManager<RememberMe<CookieSession, MemoryStore>> {
+    LoginManager::new(RememberMe::new(
+        CookieSession::new("secret"),
+        MemoryStore::new(),
+    ))
+    .refresh_view("/reauth")
+}
+
+/// The `_remember` cookie of the jar, without the session cookie.
+fn remember(jar: &str) -> String {
+    jar.split("; ")
+        .find(|c| c.starts_with("_remember="))
+        .unwrap()
+        .to_owned()
+}
+
+const HTML: (&str, &str) = ("accept", "text/html");
+
+#[tokio::test]
+async fn fresh_login_passes() {
+    let app = app(manager());
+    let jar = common::get(&app, "/login", &[]).await.jar("");
+    let res = common::get(&app, "/settings", &[HTML, ("cookie", &jar)]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "1");
+}
+
+#[tokio::test]
+async fn restored_login_is_refreshed() {
+    let app = app(manager());
+    let jar = common::get(&app, "/login", &[]).await.jar("");
+    let cookie = remember(&jar);
+
+    // the session is gone, the login is restored but not fresh
+    let res = common::get(&app, "/me", &[("cookie", &cookie)]).await;
+    assert_eq!(res.body, "1");
+    let jar = res.jar(&cookie);
+    let res = common::get(&app, "/settings", &[HTML, ("cookie", &jar)]).await;
+    assert_eq!(res.status, 303);
+    assert_eq!(res.headers["location"], "/reauth?next=%2Fsettings");
+    // still logged in
+    let res = common::get(&app, "/me", &[("cookie", &res.jar(&jar))]).await;
+    assert_eq!(res.body, "1");
+}
+
+#[tokio::test]
+async fn rejected_with_not_fresh() {
+    let app = app(manager().redirect(false));
+    let jar = common::get(&app, "/login", &[]).await.jar("");
+    let cookie = remember(&jar);
+    let res = common::get(&app, "/settings", &[("cookie", &cookie)]).await;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.body, "Fresh login required.");
+
+    // a login with the credentials again is fresh
+    let jar = common::get(&app, "/login", &[("cookie", &cookie)])
+        .await
+        .jar(&cookie);
+    let res = common::get(&app, "/settings", &[("cookie", &jar)]).await;
+    assert_eq!(res.status, 200);
+}
+
+#[tokio::test]
+async fn stale_after_fresh_for() {
+    let app = app(manager().fresh_for(time::Duration::seconds(-1)));
+    let jar = common::get(&app, "/login", &[]).await.jar("");
+    let res = common::get(&app, "/settings", &[HTML, ("cookie", &jar)]).await;
+    assert_eq!(res.status, 303);
+    assert_eq!(res.headers["location"], "/reauth?next=%2Fsettings");
+}
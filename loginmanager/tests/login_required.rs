@@ -0,0 +1,80 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{extract::FromRef, routing::get, Router};
+use loginmanager::{AuthContext, AuthUser, CookieSession, LoginManager, LoginRequired, UserMinix};
+
+#[derive(Clone)]
+struct AppState {
+    offset: i32,
+}
+
+#[derive(Clone)]
+struct Offset(i32);
+
+impl FromRef<AppState> for Offset {
+    fn from_ref(state: &AppState) -> Self {
+        Offset(state.offset)
+    }
+}
+
+/// Loaded by the `user_loader` only.
+#[derive(Clone)]
+struct User {
+    id: i32,
+}
+
+impl UserMinix for User {
+    type Key = i32;
+    type State = Offset;
+
+    fn get_id(&self) -> &i32 {
+        &self.id
+    }
+}
+
+#[tokio::test]
+async fn from_ref_loader() {
+    let state = AppState { offset: 100 };
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login(&User { id: 1 });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let api = Router::new()
+        .route("/me", get(me))
+        .route_layer(LoginRequired::<User>::new().with_state(state.clone()));
+    let app = Router::new()
+        .nest("/api", api)
+        .route("/login", get(login))
+        .with_state(state)
+        .layer(LoginManager::new(CookieSession::new("secret")).user_loader(
+            |id: i32, Offset(offset): Offset| async move { Ok(Some(User { id: id + offset })) },
+        ));
+
+    let res = common::get(&app, "/login", &[]).await;
+    let res = common::get(&app, "/api/me", &[("cookie", &res.jar(""))]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "101");
+    let res = common::get(&app, "/api/me", &[]).await;
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn missing_loader() {
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login(&User { id: 1 });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let app = Router::new()
+        .route("/me", get(me))
+        .route("/login", get(login))
+        .with_state(AppState { offset: 100 })
+        .layer(LoginManager::new(CookieSession::new("secret")));
+
+    let res = common::get(&app, "/login", &[]).await;
+    let res = common::get(&app, "/me", &[("cookie", &res.jar(""))]).await;
+    assert_eq!(res.status, 500);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{extract::Path, routing::get, Router};
+use loginmanager::{
+    AuthContext, CookieSession, LoginManager, Permission, Permissions, RequirePermission,
+    RequireRole, Role,
+};
+
+use common::User;
+
+/// User 1 is an admin who may edit posts, the others may not.
+impl Permissions for User {
+    fn has_permission(&self, permission: &str) -> bool {
+        self.id == 1 && permission == "post:edit"
+    }
+
+    fn has_role(&self, role: &str) -> bool {
+        self.id == 1 && role == "admin"
+    }
+}
+
+struct EditPost;
+
+impl Permission for EditPost {
+    const NAME: &'static str = "post:edit";
+}
+
+struct Admin;
+
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+fn app() -> Router {
+    let login = |mut auth_context: AuthContext, Path(id): Path<i32>| async move {
+        auth_context.login(&User { id });
+        "ok"
+    };
+    let edit = |RequirePermission(user, _): RequirePermission<User, EditPost>| async move {
+        user.id.to_string()
+    };
+    let admin = |RequireRole(user, _): RequireRole<User, Admin>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login/:id", get(login))
+        .route("/edit", get(edit))
+        .route("/admin", get(admin))
+        .layer(LoginManager::new(CookieSession::new("secret")))
+}
+
+const HTML: (&str, &str) = ("accept", "text/html");
+
+#[tokio::test]
+async fn granted() {
+    let app = app();
+    let jar = common::get(&app, "/login/1", &[]).await.jar("");
+    for uri in ["/edit", "/admin"] {
+        let res = common::get(&app, uri, &[HTML, ("cookie", &jar)]).await;
+        assert_eq!(res.status, 200, "{uri}");
+        assert_eq!(res.body, "1");
+    }
+}
+
+#[tokio::test]
+async fn forbidden_is_not_redirected() {
+    let app = app();
+    let jar = common::get(&app, "/login/2", &[]).await.jar("");
+    for uri in ["/edit", "/admin"] {
+        let res = common::get(&app, uri, &[HTML, ("cookie", &jar)]).await;
+        assert_eq!(res.status, 403, "{uri}");
+        assert!(!res.headers.contains_key("location"));
+        assert_eq!(res.body, "Permission denied.");
+        let res = common::get(&app, uri, &[("cookie", &jar)]).await;
+        assert_eq!(res.status, 403, "{uri}");
+    }
+    // anonymous users are still sent to the login
+    let res = common::get(&app, "/edit", &[HTML]).await;
+    assert_eq!(res.status, 303);
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "actix_layer")]
+
+use actix_web::{http::StatusCode, test, web, App};
+use async_trait::async_trait;
+use loginmanager::{
+    AuthContext, CookieSession, LoaderError, LoginManager, Permission, Permissions,
+    RequirePermission, RequireRole, Role, UserMinix,
+};
+
+#[derive(Clone)]
+struct User {
+    id: i32,
+}
+
+#[async_trait]
+impl UserMinix for User {
+    type Key = i32;
+    type State = ();
+
+    async fn load_user(id: &i32, _: &()) -> Result<Option<Self>, LoaderError> {
+        Ok(Some(User { id: *id }))
+    }
+
+    fn get_id(&self) -> &i32 {
+        &self.id
+    }
+}
+
+/// User 1 is an admin who may edit posts, the others may not.
+impl Permissions for User {
+    fn has_permission(&self, permission: &str) -> bool {
+        self.id == 1 && permission == "post:edit"
+    }
+
+    fn has_role(&self, role: &str) -> bool {
+        self.id == 1 && role == "admin"
+    }
+}
+
+struct EditPost;
+
+impl Permission for EditPost {
+    const NAME: &'static str = "post:edit";
+}
+
+struct Admin;
+
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+async fn login(mut auth_context: AuthContext, id: web::Path<i32>) -> &'static str {
+    auth_context.login(&User { id: *id });
+    "ok"
+}
+
+async fn edit(RequirePermission(user, _): RequirePermission<User, EditPost>) -> String {
+    user.id.to_string()
+}
+
+async fn admin(RequireRole(user, _): RequireRole<User, Admin>) -> String {
+    user.id.to_string()
+}
+
+#[actix_web::test]
+async fn forbidden_is_not_redirected() {
+    let app = test::init_service(
+        App::new()
+            .wrap(LoginManager::new(CookieSession::new("secret")))
+            .route("/login/{id}", web::get().to(login))
+            .route("/edit", web::get().to(edit))
+            .route("/admin", web::get().to(admin)),
+    )
+    .await;
+
+    for (id, status) in [(1, StatusCode::OK), (2, StatusCode::FORBIDDEN)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/login/{id}"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let cookie = res.response().cookies().next().unwrap().into_owned();
+        for uri in ["/edit", "/admin"] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("accept", "text/html"))
+                .cookie(cookie.clone())
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "{uri}");
+            assert!(!res.headers().contains_key("location"));
+        }
+    }
+
+    // anonymous users are still sent to the login
+    let req = test::TestRequest::get()
+        .uri("/edit")
+        .insert_header(("accept", "text/html"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, LoginManager, LoginOptions, MemoryStore, RememberMe,
+};
+use time::Duration;
+
+use common::User;
+
+fn app(store: &MemoryStore, grace: Duration) -> Router {
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login_with(&User { id: 1 }, LoginOptions { remember: true });
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login", get(login))
+        .route("/me", get(me))
+        .layer(LoginManager::new(
+            RememberMe::new(CookieSession::new("secret"), store.clone()).grace(grace),
+        ))
+}
+
+/// The `_remember` cookie of the jar, without the session cookie.
+fn remember(jar: &str) -> String {
+    jar.split("; ")
+        .find(|c| c.starts_with("_remember="))
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn concurrent_restore() {
+    let store = MemoryStore::new();
+    let app = app(&store, Duration::seconds(30));
+    let res = common::get(&app, "/login", &[]).await;
+    let cookie = remember(&res.jar(""));
+
+    // the first request rotates the token
+    let first = common::get(&app, "/me", &[("cookie", &cookie)]).await;
+    assert_eq!(first.body, "1");
+    let rotated = remember(&first.jar(&cookie));
+    assert_ne!(rotated, cookie);
+
+    // a concurrent one with the previous token is not taken for a theft
+    let second = common::get(&app, "/me", &[("cookie", &cookie)]).await;
+    assert_eq!(second.body, "1");
+    assert!(second
+        .set_cookies()
+        .iter()
+        .all(|c| !c.starts_with("_remember")));
+
+    let res = common::get(&app, "/me", &[("cookie", &rotated)]).await;
+    assert_eq!(res.body, "1");
+    assert_eq!(store.sessions().len(), 1);
+}
+
+#[tokio::test]
+async fn stolen_after_grace() {
+    let store = MemoryStore::new();
+    let app = app(&store, Duration::seconds(-1));
+    let res = common::get(&app, "/login", &[]).await;
+    let cookie = remember(&res.jar(""));
+
+    let first = common::get(&app, "/me", &[("cookie", &cookie)]).await;
+    let rotated = remember(&first.jar(&cookie));
+
+    // the previous token after the grace revokes the series
+    let res = common::get(&app, "/me", &[("cookie", &cookie)]).await;
+    assert_eq!(res.status, 401);
+    assert!(store.sessions().is_empty());
+    let res = common::get(&app, "/me", &[("cookie", &rotated)]).await;
+    assert_eq!(res.status, 401);
+}
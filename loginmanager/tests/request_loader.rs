@@ -0,0 +1,68 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{routing::get, Router};
+use loginmanager::{AuthUser, CookieSession, LoaderError, LoginManager, LoginRequired};
+
+use common::User;
+
+fn app(calls: Arc<AtomicUsize>) -> Router {
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let api = Router::new()
+        .route("/me", get(me))
+        .route_layer(LoginRequired::<User>::new());
+    Router::new()
+        .nest("/api", api)
+        .route("/public", get(|| async { "public" }))
+        .layer(
+            LoginManager::new(CookieSession::new("secret")).request_loader(
+                move |parts: &axum::http::request::Parts| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let token = parts.headers.get("x-token").cloned();
+                    async move {
+                        match token.as_ref().map(|token| token.as_bytes()) {
+                            Some(b"secret") => Ok(Some(User { id: 7 })),
+                            Some(_) => Err(LoaderError::new(std::io::Error::other("down"))),
+                            None => Ok(None),
+                        }
+                    }
+                },
+            ),
+        )
+}
+
+#[tokio::test]
+async fn route_without_user() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = app(calls.clone());
+
+    let res = common::get(&app, "/public", &[("x-token", "broken")]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "public");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn header_token() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = app(calls.clone());
+
+    // `LoginRequired` and `AuthUser` both need the user
+    let res = common::get(&app, "/api/me", &[("x-token", "secret")]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.body, "7");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(res.set_cookies().is_empty());
+
+    let res = common::get(&app, "/api/me", &[]).await;
+    assert_eq!(res.status, 401);
+    let res = common::get(&app, "/api/me", &[("x-token", "broken")]).await;
+    assert_eq!(res.status, 500);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
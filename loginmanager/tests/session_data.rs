@@ -0,0 +1,107 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{extract::Path, routing::get, Router};
+use loginmanager::{
+    AuthContext, AuthUser, CookieSession, DecodeRequest, LoginManager, MemoryStore, SessionData,
+    StoreSession,
+};
+
+use common::User;
+
+fn app<D>(decoder: D) -> Router
+where
+    D: DecodeRequest<axum::http::Request<axum::body::Body>, axum::response::Response>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let login = |mut auth_context: AuthContext| async move {
+        auth_context.login(&User { id: 1 });
+        "ok"
+    };
+    let logout = |mut auth_context: AuthContext| async move {
+        auth_context.logout();
+        "ok"
+    };
+    let set = |mut data: SessionData, Path((key, value)): Path<(String, String)>| async move {
+        data.insert(&key, &value).unwrap();
+        "ok"
+    };
+    let get_ = |data: SessionData, Path(key): Path<String>| async move {
+        data.get::<String>(&key).unwrap_or_default()
+    };
+    let clear = |mut data: SessionData| async move {
+        data.clear();
+        "ok"
+    };
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    Router::new()
+        .route("/login", get(login))
+        .route("/logout", get(logout))
+        .route("/set/:key/:value", get(set))
+        .route("/get/:key", get(get_))
+        .route("/clear", get(clear))
+        .route("/me", get(me))
+        .layer(LoginManager::new(decoder))
+}
+
+fn apps() -> [Router; 2] {
+    [
+        app(CookieSession::new("secret")),
+        app(StoreSession::new(MemoryStore::new())),
+    ]
+}
+
+/// Send a request with the cookies of `jar`, return the body and the new jar.
+async fn visit(app: &Router, uri: &str, jar: &str) -> (String, String) {
+    let res = common::get(app, uri, &[("cookie", jar)]).await;
+    assert_eq!(res.status, 200, "{uri}");
+    (res.body.clone(), res.jar(jar))
+}
+
+#[tokio::test]
+async fn persists_across_requests() {
+    for app in apps() {
+        let (_, jar) = visit(&app, "/set/locale/en", "").await;
+        let (_, jar) = visit(&app, "/set/tenant/7", &jar).await;
+        assert_eq!(visit(&app, "/get/locale", &jar).await.0, "en");
+        assert_eq!(visit(&app, "/get/tenant", &jar).await.0, "7");
+
+        // kept through a login, which renews the session
+        let (_, jar) = visit(&app, "/login", &jar).await;
+        assert_eq!(visit(&app, "/me", &jar).await.0, "1");
+        let (_, jar) = visit(&app, "/set/locale/fr", &jar).await;
+        assert_eq!(visit(&app, "/get/locale", &jar).await.0, "fr");
+        assert_eq!(visit(&app, "/me", &jar).await.0, "1");
+    }
+}
+
+#[tokio::test]
+async fn survives_logout() {
+    for app in apps() {
+        let (_, jar) = visit(&app, "/login", "").await;
+        let (_, jar) = visit(&app, "/set/locale/en", &jar).await;
+        let (_, jar) = visit(&app, "/logout", &jar).await;
+        let res = common::get(&app, "/me", &[("cookie", &jar)]).await;
+        assert_eq!(res.status, 401);
+        assert_eq!(visit(&app, "/get/locale", &jar).await.0, "en");
+    }
+}
+
+#[tokio::test]
+async fn clear_keeps_the_user() {
+    for app in apps() {
+        let (_, jar) = visit(&app, "/login", "").await;
+        let (_, jar) = visit(&app, "/set/locale/en", &jar).await;
+        let (_, jar) = visit(&app, "/set/tenant/7", &jar).await;
+        let (_, jar) = visit(&app, "/clear", &jar).await;
+        assert_eq!(visit(&app, "/get/locale", &jar).await.0, "");
+        assert_eq!(visit(&app, "/get/tenant", &jar).await.0, "");
+        assert_eq!(visit(&app, "/me", &jar).await.0, "1");
+        let (_, jar) = visit(&app, "/set/locale/de", &jar).await;
+        assert_eq!(visit(&app, "/get/locale", &jar).await.0, "de");
+    }
+}
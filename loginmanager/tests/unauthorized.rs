@@ -0,0 +1,52 @@
+#![cfg(feature = "axum_layer")]
+
+mod common;
+
+use axum::{http::StatusCode, routing::get, Router};
+use loginmanager::{AuthUser, CookieSession, LoginManager};
+
+use common::User;
+
+fn app(loginmanager: LoginManager<CookieSession>) -> Router {
+    let me = |AuthUser(user): AuthUser<User>| async move { user.id.to_string() };
+    let own = || async { (StatusCode::UNAUTHORIZED, "bad signature") };
+    Router::new()
+        .route("/me", get(me))
+        .route("/webhook", get(own))
+        .layer(loginmanager)
+}
+
+const HTML: (&str, &str) = ("accept", "text/html");
+const HTMX: (&str, &str) = ("hx-request", "true");
+
+#[tokio::test]
+async fn every_401_is_answered() {
+    let app = app(LoginManager::new(CookieSession::new("secret")));
+    for uri in ["/me", "/webhook"] {
+        let res = common::get(&app, uri, &[HTML]).await;
+        assert_eq!(res.status, 303);
+        let res = common::get(&app, uri, &[]).await;
+        assert_eq!(res.status, 401);
+        assert!(res.body.contains("login_url"));
+    }
+}
+
+#[tokio::test]
+async fn htmx_gets_200() {
+    let app = app(LoginManager::new(CookieSession::new("secret")));
+    let res = common::get(&app, "/me", &[HTMX]).await;
+    assert_eq!(res.status, 200);
+    assert_eq!(res.headers["hx-redirect"], "/login?next=%2Fme");
+}
+
+#[tokio::test]
+async fn rejections_only_keeps_app_401() {
+    let app = app(LoginManager::new(CookieSession::new("secret")).rejections_only());
+    let res = common::get(&app, "/me", &[HTML]).await;
+    assert_eq!(res.status, 303);
+    let res = common::get(&app, "/webhook", &[HTML]).await;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.body, "bad signature");
+    let res = common::get(&app, "/webhook", &[]).await;
+    assert_eq!(res.body, "bad signature");
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "actix_layer")]
+
+use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+use async_trait::async_trait;
+use loginmanager::{AuthUser, CookieSession, LoaderError, LoginManager, UserMinix};
+
+#[derive(Clone)]
+struct User {
+    id: i32,
+}
+
+#[async_trait]
+impl UserMinix for User {
+    type Key = i32;
+    type State = ();
+
+    async fn load_user(id: &i32, _: &()) -> Result<Option<Self>, LoaderError> {
+        Ok(Some(User { id: *id }))
+    }
+
+    fn get_id(&self) -> &i32 {
+        &self.id
+    }
+}
+
+async fn me(AuthUser(user): AuthUser<User>) -> String {
+    user.id.to_string()
+}
+
+async fn webhook() -> HttpResponse {
+    HttpResponse::Unauthorized().body("bad signature")
+}
+
+#[actix_web::test]
+async fn every_401_is_answered() {
+    let app = test::init_service(
+        App::new()
+            .wrap(LoginManager::new(CookieSession::new("secret")))
+            .route("/me", web::get().to(me))
+            .route("/webhook", web::get().to(webhook)),
+    )
+    .await;
+
+    for uri in ["/me", "/webhook"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("accept", "text/html"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/me")
+        .insert_header(("hx-request", "true"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("hx-redirect").unwrap(),
+        "/login?next=%2Fme"
+    );
+}
+
+#[actix_web::test]
+async fn rejections_only_keeps_app_401() {
+    let app = test::init_service(
+        App::new()
+            .wrap(LoginManager::new(CookieSession::new("secret")).rejections_only())
+            .route("/me", web::get().to(me))
+            .route("/webhook", web::get().to(webhook)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/me")
+        .insert_header(("accept", "text/html"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/webhook")
+        .insert_header(("accept", "text/html"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::read_body(res).await, "bad signature");
+}